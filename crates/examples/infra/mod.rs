@@ -1,16 +1,20 @@
 #![allow(clippy::panic)]
+use anyhow::Context;
 use async_compatibility_layer::art::async_sleep;
 use async_compatibility_layer::logging::{setup_backtrace, setup_logging};
 use async_lock::RwLock;
 use async_trait::async_trait;
 use cdn_broker::reexports::crypto::signature::KeyPair;
 use cdn_broker::reexports::message::Topic;
+use cdn_broker::Broker;
+use cdn_marshal::Marshal;
 use chrono::Utc;
 use clap::Parser;
 use clap::{Arg, Command};
 use futures::StreamExt;
 use hotshot::traits::implementations::{
-    CombinedNetworks, PushCdnNetwork, UnderlyingCombinedNetworks, WrappedSignatureKey,
+    CombinedNetworks, DiscoveryBackend, DiscoveryEndpoint, ProductionDef, PushCdnNetwork,
+    TestingDef, UnderlyingCombinedNetworks, WrappedSignatureKey,
 };
 use hotshot::traits::BlockPayload;
 use hotshot::{
@@ -188,6 +192,20 @@ pub fn read_orchestrator_init_config<TYPES: NodeType>() -> (
                 .help("Sets the url of the da webserver")
                 .required(false),
         )
+        .arg(
+            Arg::new("cdn_marshal_address")
+                .long("cdn_marshal_address")
+                .value_name("ADDRESS")
+                .help("Sets the address of the Push CDN marshal, as host:port")
+                .required(false),
+        )
+        .arg(
+            Arg::new("cdn_discovery_endpoint")
+                .long("cdn_discovery_endpoint")
+                .value_name("ENDPOINT")
+                .help("Sets the discovery endpoint of the Push CDN brokers and marshal")
+                .required(false),
+        )
         .get_matches();
 
     if let Some(config_file_string) = matches.get_one::<String>("config_file") {
@@ -242,6 +260,13 @@ pub fn read_orchestrator_init_config<TYPES: NodeType>() -> (
         };
         config.da_web_server_config = Some(updated_da_web_server_config);
     }
+    if let Some(cdn_marshal_address_string) = matches.get_one::<String>("cdn_marshal_address") {
+        config.cdn_marshal_address = Some(cdn_marshal_address_string.clone());
+    }
+    if let Some(cdn_discovery_endpoint_string) = matches.get_one::<String>("cdn_discovery_endpoint")
+    {
+        config.cdn_discovery_endpoint = Some(cdn_discovery_endpoint_string.clone());
+    }
 
     (config, orchestrator_url)
 }
@@ -860,6 +885,7 @@ where
                 .expect("`cdn_marshal_address` needs to be supplied for a push CDN run"),
             topics.iter().map(ToString::to_string).collect(),
             keypair,
+            None,
         )
         .await
         .expect("failed to perform initial client connection");
@@ -884,6 +910,104 @@ where
     }
 }
 
+/// Where and how a Push CDN broker runs
+pub struct CdnBrokerArgs<TYPES: NodeType> {
+    /// The discovery endpoint shared with the other brokers and the marshal
+    pub discovery_endpoint: DiscoveryEndpoint,
+    /// The address to bind to for connections from users
+    pub public_bind_address: String,
+    /// The address users are told to connect to
+    pub public_advertise_address: String,
+    /// The address to bind to for connections from other brokers
+    pub private_bind_address: String,
+    /// The address other brokers are told to connect to
+    pub private_advertise_address: String,
+    /// Whether or not metric collection and serving is enabled
+    pub metrics_enabled: bool,
+    /// The IP and port to serve metrics on, if not the default
+    pub metrics_address: Option<(String, u16)>,
+    /// The keypair the brokers authenticate to each other with
+    pub keypair: KeyPair<WrappedSignatureKey<TYPES::SignatureKey>>,
+}
+
+/// Runs a Push CDN broker until it stops, using the discovery backend its endpoint names
+///
+/// # Errors
+/// If the broker fails to start or stops with an error
+pub async fn run_cdn_broker<TYPES: NodeType>(args: CdnBrokerArgs<TYPES>) -> anyhow::Result<()> {
+    let discovery_backend = args.discovery_endpoint.backend();
+
+    let mut builder = cdn_broker::ConfigBuilder::default();
+    builder
+        .discovery_endpoint(args.discovery_endpoint.to_string())
+        .public_bind_address(args.public_bind_address)
+        .public_advertise_address(args.public_advertise_address)
+        .private_bind_address(args.private_bind_address)
+        .private_advertise_address(args.private_advertise_address)
+        .metrics_enabled(args.metrics_enabled)
+        .keypair(args.keypair);
+    if let Some((metrics_ip, metrics_port)) = args.metrics_address {
+        builder.metrics_ip(metrics_ip).metrics_port(metrics_port);
+    }
+    let config: cdn_broker::Config<WrappedSignatureKey<TYPES::SignatureKey>> =
+        builder.build().context("failed to build broker config")?;
+
+    // Uses TCP for broker connections and Quic for user connections
+    match discovery_backend {
+        DiscoveryBackend::Redis => {
+            Broker::<ProductionDef<TYPES>>::new(config)
+                .await?
+                .start()
+                .await?;
+        }
+        DiscoveryBackend::Embedded => {
+            Broker::<TestingDef<TYPES>>::new(config)
+                .await?
+                .start()
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a Push CDN marshal on `bind_address` until it stops, using the discovery backend
+/// `discovery_endpoint` names
+///
+/// # Errors
+/// If the marshal fails to start or stops with an error
+pub async fn run_cdn_marshal<TYPES: NodeType>(
+    bind_address: String,
+    discovery_endpoint: DiscoveryEndpoint,
+    metrics_enabled: bool,
+) -> anyhow::Result<()> {
+    let discovery_backend = discovery_endpoint.backend();
+
+    let config = cdn_marshal::ConfigBuilder::default()
+        .bind_address(bind_address)
+        .discovery_endpoint(discovery_endpoint.to_string())
+        .metrics_enabled(metrics_enabled)
+        .build()
+        .context("failed to build marshal config")?;
+
+    match discovery_backend {
+        DiscoveryBackend::Redis => {
+            Marshal::<ProductionDef<TYPES>>::new(config)
+                .await?
+                .start()
+                .await?;
+        }
+        DiscoveryBackend::Embedded => {
+            Marshal::<TestingDef<TYPES>>::new(config)
+                .await?
+                .start()
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
 // Libp2p
 
 /// Represents a libp2p-based run
@@ -1022,6 +1146,7 @@ where
                 .expect("`cdn_marshal_address` needs to be supplied for a CDN run"),
             topics.iter().map(ToString::to_string).collect(),
             keypair,
+            None,
         )
         .await
         .expect("failed to perform intiail client connection");
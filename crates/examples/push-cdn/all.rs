@@ -2,24 +2,31 @@
 /// The types we're importing
 pub mod types;
 
-use crate::infra::{read_orchestrator_init_config, run_orchestrator, OrchestratorArgs};
+use crate::infra::{
+    read_orchestrator_init_config, run_cdn_broker, run_cdn_marshal, run_orchestrator,
+    CdnBrokerArgs, OrchestratorArgs,
+};
 use crate::types::{DANetwork, NodeImpl, QuorumNetwork, ThisRun};
 use async_compatibility_layer::art::async_spawn;
 use cdn_broker::reexports::crypto::signature::KeyPair;
-use cdn_broker::Broker;
-use cdn_marshal::Marshal;
-use hotshot::traits::implementations::{TestingDef, WrappedSignatureKey};
+use futures::{channel::mpsc, future::Either, StreamExt};
+use hotshot::traits::implementations::{DiscoveryEndpoint, WrappedSignatureKey};
 use hotshot::types::SignatureKey;
 use hotshot_example_types::state_types::TestTypes;
 use hotshot_orchestrator::client::ValidatorArgs;
+use hotshot_task_impls::helpers::cancel_task;
 use hotshot_types::traits::node_implementation::NodeType;
-use std::net::{IpAddr, Ipv4Addr};
+use std::{
+    fs,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+};
 
 /// The infra implementation
 #[path = "../infra/mod.rs"]
 pub mod infra;
 
-use tracing::error;
+use tracing::{error, info};
 
 #[cfg_attr(async_executor_impl = "tokio", tokio::main)]
 #[cfg_attr(async_executor_impl = "async-std", async_std::main)]
@@ -29,7 +36,21 @@ async fn main() {
     setup_backtrace();
 
     // use configfile args
-    let (config, orchestrator_url) = read_orchestrator_init_config::<TestTypes>();
+    let (mut config, orchestrator_url) = read_orchestrator_init_config::<TestTypes>();
+
+    // The marshal binds to the address the nodes are told to connect to. Nodes on other
+    // machines need it to be one they can reach.
+    let marshal_endpoint = config
+        .cdn_marshal_address
+        .get_or_insert_with(|| "127.0.0.1:9000".to_string())
+        .clone();
+
+    // The broker (peer) discovery endpoint defaults to a local SQLite file
+    let discovery_endpoint: DiscoveryEndpoint = config
+        .cdn_discovery_endpoint
+        .get_or_insert_with(|| "test.sqlite".to_string())
+        .parse()
+        .expect("invalid discovery endpoint");
 
     // Start the orhcestrator
     async_spawn(run_orchestrator::<
@@ -48,8 +69,15 @@ async fn main() {
     let (broker_public_key, broker_private_key) =
         <TestTypes as NodeType>::SignatureKey::generated_from_seed_indexed([0u8; 32], 1337);
 
-    // The broker (peer) discovery endpoint shall be a local SQLite file
-    let discovery_endpoint = "test.sqlite".to_string();
+    // Stop everything cleanly on Ctrl-C, so the example can be re-run without cleaning up
+    let (ctrl_c_sender, mut ctrl_c) = mpsc::unbounded();
+    ctrlc::set_handler(move || {
+        let _ = ctrl_c_sender.unbounded_send(());
+    })
+    .expect("failed to install Ctrl-C handler");
+
+    // The brokers and the marshal
+    let mut cdn_tasks = Vec::new();
 
     // 2 brokers
     for _ in 0..2 {
@@ -61,56 +89,37 @@ async fn main() {
         let private_address = format!("127.0.0.1:{private_port}");
         let public_address = format!("127.0.0.1:{public_port}");
 
-        let config: cdn_broker::Config<WrappedSignatureKey<<TestTypes as NodeType>::SignatureKey>> =
-            cdn_broker::ConfigBuilder::default()
-                .discovery_endpoint(discovery_endpoint.clone())
-                .keypair(KeyPair {
-                    public_key: WrappedSignatureKey(broker_public_key),
-                    private_key: broker_private_key.clone(),
-                })
-                .metrics_enabled(false)
-                .private_bind_address(private_address.clone())
-                .public_bind_address(public_address.clone())
-                .private_advertise_address(private_address)
-                .public_advertise_address(public_address)
-                .build()
-                .expect("failed to build broker config");
-
-        // Create and spawn the broker
-        async_spawn(async move {
-            let broker: Broker<TestingDef<TestTypes>> =
-                Broker::new(config).await.expect("broker failed to start");
-
-            // Error if we stopped unexpectedly
-            if let Err(err) = broker.start().await {
+        let args = CdnBrokerArgs::<TestTypes> {
+            discovery_endpoint: discovery_endpoint.clone(),
+            public_bind_address: public_address.clone(),
+            public_advertise_address: public_address,
+            private_bind_address: private_address.clone(),
+            private_advertise_address: private_address,
+            metrics_enabled: false,
+            metrics_address: None,
+            keypair: KeyPair {
+                public_key: WrappedSignatureKey(broker_public_key),
+                private_key: broker_private_key.clone(),
+            },
+        };
+
+        // Spawn the broker, erroring if it stops unexpectedly
+        cdn_tasks.push(async_spawn(async move {
+            if let Err(err) = run_cdn_broker(args).await {
                 error!("broker stopped: {err}");
             }
-        });
+        }));
     }
 
-    // Get the port to use for the marshal
-    let marshal_port = 9000;
-
-    // Configure the marshal
-    let marshal_endpoint = format!("127.0.0.1:{marshal_port}");
-    let marshal_config = cdn_marshal::ConfigBuilder::default()
-        .bind_address(marshal_endpoint.clone())
-        .discovery_endpoint("test.sqlite".to_string())
-        .metrics_enabled(false)
-        .build()
-        .expect("failed to build marshal config");
-
-    // Spawn the marshal
-    async_spawn(async move {
-        let marshal: Marshal<TestingDef<TestTypes>> = Marshal::new(marshal_config)
-            .await
-            .expect("failed to spawn marshal");
-
-        // Error if we stopped unexpectedly
-        if let Err(err) = marshal.start().await {
-            error!("broker stopped: {err}");
+    // Spawn the marshal, erroring if it stops unexpectedly
+    let marshal_discovery_endpoint = discovery_endpoint.clone();
+    cdn_tasks.push(async_spawn(async move {
+        if let Err(err) =
+            run_cdn_marshal::<TestTypes>(marshal_endpoint, marshal_discovery_endpoint, false).await
+        {
+            error!("marshal stopped: {err}");
         }
-    });
+    }));
 
     // Start the proper number of nodes
     let mut nodes = Vec::new();
@@ -128,5 +137,31 @@ async fn main() {
         });
         nodes.push(node);
     }
-    let _result = futures::future::join_all(nodes).await;
+
+    // Run until the nodes finish or we're interrupted
+    let interrupted = matches!(
+        futures::future::select(futures::future::join_all(nodes.iter_mut()), ctrl_c.next()).await,
+        Either::Right(_)
+    );
+    if interrupted {
+        info!("interrupted, shutting down");
+        for node in nodes {
+            cancel_task(node).await;
+        }
+    }
+
+    // Shut down the CDN and clean up its discovery database (and SQLite's journal files)
+    for task in cdn_tasks {
+        cancel_task(task).await;
+    }
+    if let DiscoveryEndpoint::Sqlite(database) = &discovery_endpoint {
+        for suffix in ["", "-shm", "-wal", "-journal"] {
+            let path = format!("{}{suffix}", database.display());
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != ErrorKind::NotFound {
+                    error!("failed to remove {path}: {err}");
+                }
+            }
+        }
+    }
 }
@@ -2,24 +2,29 @@
 //! a `Broker` object.
 
 use anyhow::{Context, Result};
-use cdn_broker::{reexports::crypto::signature::KeyPair, Broker, Config, ConfigBuilder};
+use cdn_broker::reexports::crypto::signature::KeyPair;
 use clap::Parser;
-use hotshot::traits::implementations::{ProductionDef, WrappedSignatureKey};
+use hotshot::traits::implementations::{DiscoveryEndpoint, WrappedSignatureKey};
 use hotshot::types::SignatureKey;
 use hotshot_example_types::node_types::TestTypes;
 use hotshot_types::traits::node_implementation::NodeType;
+use infra::CdnBrokerArgs;
 use local_ip_address::local_ip;
 use sha2::Digest;
 
+/// general infra used for this example
+#[path = "../infra/mod.rs"]
+pub mod infra;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 /// The main component of the push CDN.
 struct Args {
     /// The discovery client endpoint (including scheme) to connect to.
-    /// With the local discovery feature, this is a file path.
-    /// With the remote (redis) discovery feature, this is a redis URL (e.g. `redis://127.0.0.1:6789`).
+    /// A redis URL (e.g. `redis://127.0.0.1:6789`) uses Redis for discovery.
+    /// Anything else is treated as a file path for an embedded (`SQLite`) database.
     #[arg(short, long)]
-    discovery_endpoint: String,
+    discovery_endpoint: DiscoveryEndpoint,
 
     /// Whether or not metric collection and serving is enabled
     #[arg(long, default_value_t = true)]
@@ -68,30 +73,19 @@ async fn main() -> Result<()> {
     let (public_key, private_key) =
         <TestTypes as NodeType>::SignatureKey::generated_from_seed_indexed(key_hash.into(), 1337);
 
-    // Create a broker configuration with all the supplied arguments
-    let broker_config: Config<WrappedSignatureKey<<TestTypes as NodeType>::SignatureKey>> =
-        ConfigBuilder::default()
-            .public_advertise_address(args.public_advertise_address)
-            .public_bind_address(format!("0.0.0.0:{}", args.public_bind_port))
-            .private_advertise_address(private_address.clone())
-            .private_bind_address(private_address)
-            .metrics_enabled(args.metrics_enabled)
-            .metrics_ip(args.metrics_ip)
-            .discovery_endpoint(args.discovery_endpoint)
-            .metrics_port(args.metrics_port)
-            .keypair(KeyPair {
-                public_key: WrappedSignatureKey(public_key),
-                private_key,
-            })
-            .build()
-            .with_context(|| "failed to build broker config")?;
-
-    // Create new `Broker`
-    // Uses TCP from broker connections and Quic for user connections.
-    let broker = Broker::<ProductionDef<TestTypes>>::new(broker_config).await?;
-
-    // Start the main loop, consuming it
-    broker.start().await?;
-
-    Ok(())
+    // Run the broker with all the supplied arguments until it stops
+    infra::run_cdn_broker::<TestTypes>(CdnBrokerArgs {
+        discovery_endpoint: args.discovery_endpoint,
+        public_bind_address: format!("0.0.0.0:{}", args.public_bind_port),
+        public_advertise_address: args.public_advertise_address,
+        private_bind_address: private_address.clone(),
+        private_advertise_address: private_address,
+        metrics_enabled: args.metrics_enabled,
+        metrics_address: Some((args.metrics_ip, args.metrics_port)),
+        keypair: KeyPair {
+            public_key: WrappedSignatureKey(public_key),
+            private_key,
+        },
+    })
+    .await
 }
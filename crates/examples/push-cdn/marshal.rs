@@ -1,25 +1,32 @@
 //! The following is the main `Marshal` binary, which just instantiates and runs
 //! a `Marshal` object with the `HotShot` types.
 //!
-use anyhow::{Context, Result};
-use cdn_marshal::{ConfigBuilder, Marshal};
+use anyhow::Result;
 use clap::Parser;
-use hotshot::traits::implementations::ProductionDef;
+use hotshot::traits::implementations::DiscoveryEndpoint;
 use hotshot_example_types::node_types::TestTypes;
 
+/// general infra used for this example
+#[path = "../infra/mod.rs"]
+pub mod infra;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 /// The main component of the push CDN.
 struct Args {
     /// The discovery client endpoint (including scheme) to connect to.
-    /// With the local discovery feature, this is a file path.
-    /// With the remote (redis) discovery feature, this is a redis URL (e.g. `redis://127.0.0.1:6789`).
+    /// A redis URL (e.g. `redis://127.0.0.1:6789`) uses Redis for discovery.
+    /// Anything else is treated as a file path for an embedded (`SQLite`) database.
     #[arg(short, long)]
-    discovery_endpoint: String,
+    discovery_endpoint: DiscoveryEndpoint,
 
     /// The port to bind to for connections (from users)
     #[arg(short, long, default_value_t = 8082)]
     bind_port: u16,
+
+    /// Whether or not metric collection and serving is enabled
+    #[arg(long, default_value_t = false)]
+    metrics_enabled: bool,
 }
 
 #[cfg_attr(async_executor_impl = "tokio", tokio::main)]
@@ -31,18 +38,11 @@ async fn main() -> Result<()> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
 
-    // Create a new `Config`
-    let config = ConfigBuilder::default()
-        .bind_address(format!("0.0.0.0:{}", args.bind_port))
-        .discovery_endpoint(args.discovery_endpoint)
-        .build()
-        .with_context(|| "failed to build Marshal config")?;
-
-    // Create new `Marshal` from the config
-    let marshal = Marshal::<ProductionDef<TestTypes>>::new(config).await?;
-
-    // Start the main loop, consuming it
-    marshal.start().await?;
-
-    Ok(())
+    // Run the marshal until it stops
+    infra::run_cdn_marshal::<TestTypes>(
+        format!("0.0.0.0:{}", args.bind_port),
+        args.discovery_endpoint,
+        args.metrics_enabled,
+    )
+    .await
 }
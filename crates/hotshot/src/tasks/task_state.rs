@@ -7,6 +7,7 @@ use hotshot_task_impls::{
     view_sync::ViewSyncTaskState,
 };
 use hotshot_types::constants::VERSION_0_1;
+use hotshot_types::simple_certificate::QcCache;
 use hotshot_types::traits::{
     consensus_api::ConsensusApi,
     node_implementation::{ConsensusTime, NodeImplementation, NodeType},
@@ -202,6 +203,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> CreateTaskState<TYPES, I>
             committee_network: handle.hotshot.networks.da_network.clone(),
             timeout_membership: handle.hotshot.memberships.quorum_membership.clone().into(),
             quorum_membership: handle.hotshot.memberships.quorum_membership.clone().into(),
+            justify_qc_cache: QcCache::default(),
             committee_membership: handle.hotshot.memberships.da_membership.clone().into(),
             storage: handle.storage.clone(),
         }
@@ -13,10 +13,24 @@ pub mod implementations {
         combined_network::{CombinedNetworks, UnderlyingCombinedNetworks},
         libp2p_network::{Libp2pNetwork, PeerInfoVec},
         memory_network::{MasterMap, MemoryNetwork},
-        push_cdn_network::{
-            KeyPair, ProductionDef, PushCdnNetwork, TestingDef, WrappedSignatureKey,
+        push_cdn::{
+            decode_frame, deserialize_versioned, parse_topic, ConnectionEvent, ConnectionTuning,
+            DiscoveryBackend, DiscoveryEndpoint, InboundRateLimit, KeyPair, MessageOrigin,
+            ProductionDef, PushCdnClient, PushCdnNetwork, RateLimit, RetryPolicy, SendPriority,
+            SendQueueConfig, TestingDef, Topic, WrappedSignatureKey, KNOWN_TOPICS,
+            MAX_QUEUED_LOOKUPS,
+        },
+        recording_network::{
+            read_recording, FileSink, MemorySink, RecordingNetwork, ReplayNetwork, TrafficEvent,
+            TrafficKind, TrafficSink,
         },
         web_server_network::WebServerNetwork,
         NetworkingMetricsValue,
     };
+
+    #[cfg(feature = "hotshot-testing")]
+    pub use super::networking::{
+        mock_push_cdn_network::{MockPushCdn, MockPushCdnNetwork},
+        push_cdn::{PushCdnTestConfig, ReceiveOrdering, TopicAssignment},
+    };
 }
@@ -2,7 +2,7 @@
 use ethereum_types::U256;
 use hotshot_types::signature_key::BLSPubKey;
 use hotshot_types::traits::{
-    election::{ElectionConfig, Membership},
+    election::{commit_to_stake_table, ElectionConfig, Membership, StakeTableCommitment},
     node_implementation::NodeType,
     signature_key::{SignatureKey, StakeTableEntryType},
 };
@@ -22,6 +22,8 @@ pub struct GeneralStaticCommittee<T, PUBKEY: SignatureKey> {
     nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
     /// The nodes on the static committee and their stake
     committee_nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
+    /// The commitment to `committee_nodes_with_stake`
+    committee_stake_table_commitment: StakeTableCommitment,
     /// builder nodes
     committee_nodes_without_stake: Vec<PUBKEY>,
     /// Node type phantom
@@ -41,6 +43,7 @@ impl<T, PUBKEY: SignatureKey> GeneralStaticCommittee<T, PUBKEY> {
     ) -> Self {
         Self {
             nodes_with_stake: nodes_with_stake.clone(),
+            committee_stake_table_commitment: commit_to_stake_table(&nodes_with_stake),
             committee_nodes_with_stake: nodes_with_stake,
             committee_nodes_without_stake: nodes_without_stake,
             _type_phantom: PhantomData,
@@ -69,6 +72,10 @@ where
         self.committee_nodes_with_stake.clone()
     }
 
+    fn get_committee_qc_stake_table_commitment(&self) -> StakeTableCommitment {
+        self.committee_stake_table_commitment
+    }
+
     #[cfg(not(feature = "randomized-leader-election"))]
     /// Index the vector of public keys with the current view number
     fn get_leader(&self, view_number: TYPES::Time) -> PUBKEY {
@@ -141,6 +148,7 @@ where
         committee_nodes_without_stake.truncate(config.num_nodes_without_stake.try_into().unwrap());
         Self {
             nodes_with_stake,
+            committee_stake_table_commitment: commit_to_stake_table(&committee_nodes_with_stake),
             committee_nodes_with_stake,
             committee_nodes_without_stake,
             _type_phantom: PhantomData,
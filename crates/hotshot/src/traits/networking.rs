@@ -4,12 +4,19 @@
 //! trait. Currently this includes
 //! - [`MemoryNetwork`](memory_network::MemoryNetwork), an in memory testing-only implementation
 //! - [`Libp2pNetwork`](libp2p_network::Libp2pNetwork), a production-ready networking implementation built on top of libp2p-rs.
+//! - [`PushCdnNetwork`](push_cdn::PushCdnNetwork), a client of the Push CDN, and an in-process testing-only stand-in for it
+//! - [`RecordingNetwork`](recording_network::RecordingNetwork) and [`ReplayNetwork`](recording_network::ReplayNetwork), which record any network's traffic and replay it deterministically
 
 pub mod combined_network;
 pub mod libp2p_network;
 pub mod memory_network;
+/// An in-process stand-in for the Push CDN network, for tests
+#[cfg(feature = "hotshot-testing")]
+pub mod mock_push_cdn_network;
 /// The Push CDN network
-pub mod push_cdn_network;
+pub mod push_cdn;
+/// Networks that record traffic, and replay it deterministically
+pub mod recording_network;
 pub mod web_server_network;
 use std::{
     collections::HashMap,
@@ -1,6 +1,6 @@
 //! Networking Implementation that has a primary and a fallback network.  If the primary
 //! Errors we will use the backup to send or receive
-use super::{push_cdn_network::PushCdnNetwork, NetworkError};
+use super::{push_cdn::PushCdnNetwork, NetworkError};
 use crate::traits::implementations::Libp2pNetwork;
 use async_lock::RwLock;
 use hotshot_types::{
@@ -0,0 +1,293 @@
+//! An in-process stand-in for the Push CDN
+//!
+//! This module provides a [`MockPushCdnNetwork`], which routes messages the way the Push CDN
+//! does (broadcasts by topic, direct messages by public key) but over in-process channels, with
+//! no brokers, marshal, or sockets. It is useful for fast tests that only care about routing.
+
+use super::{
+    push_cdn::{deserialize_versioned, Topic},
+    NetworkError, NetworkReliability,
+};
+use async_compatibility_layer::channel::{unbounded, UnboundedReceiver, UnboundedSender};
+use async_lock::RwLock;
+use async_trait::async_trait;
+use hotshot_types::{
+    boxed_sync,
+    message::Message,
+    traits::{
+        network::{AsyncGenerator, ConnectedNetwork, TestableNetworkingImplementation},
+        node_implementation::NodeType,
+        signature_key::SignatureKey,
+    },
+    BoxSyncFuture,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::warn;
+use versioned_binary_serialization::{version::StaticVersionType, BinarySerializer, Serializer};
+
+/// A connected client, as the mock CDN sees it
+struct MockClient {
+    /// The topics the client is subscribed to
+    topics: BTreeSet<Topic>,
+    /// Where to deliver messages for the client
+    sender: UnboundedSender<Vec<u8>>,
+}
+
+/// The shared "CDN" that every [`MockPushCdnNetwork`] in a group is connected to. Keeps track
+/// of which clients are connected and what they are subscribed to.
+pub struct MockPushCdn<K: SignatureKey> {
+    /// Every connected client, by public key
+    clients: RwLock<BTreeMap<K, MockClient>>,
+}
+
+impl<K: SignatureKey> MockPushCdn<K> {
+    /// Create a new mock CDN with no clients connected to it
+    #[must_use]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            clients: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Deliver `message` to every client subscribed to `topic`
+    async fn broadcast(&self, topic: &Topic, message: &[u8]) {
+        for client in self.clients.read().await.values() {
+            if client.topics.contains(topic) {
+                // A closed channel means the client is shutting down
+                let _ = client.sender.send(message.to_vec()).await;
+            }
+        }
+    }
+
+    /// Deliver `message` to the client with public key `recipient`
+    ///
+    /// # Errors
+    /// If no client with that public key is connected
+    async fn direct(&self, recipient: &K, message: Vec<u8>) -> Result<(), NetworkError> {
+        let clients = self.clients.read().await;
+        let client = clients.get(recipient).ok_or(NetworkError::NoSuchNode)?;
+        client
+            .sender
+            .send(message)
+            .await
+            .map_err(|_| NetworkError::CouldNotDeliver)
+    }
+}
+
+/// An in-process implementation of the Push CDN network. Broadcasts are delivered to every
+/// client connected to the same [`MockPushCdn`] that is subscribed to the topic, and direct
+/// messages to the client with the recipient's public key.
+#[derive(Clone)]
+pub struct MockPushCdnNetwork<TYPES: NodeType> {
+    /// Our public key, which other clients address direct messages to
+    public_key: TYPES::SignatureKey,
+    /// The mock CDN we are connected to
+    cdn: Arc<MockPushCdn<TYPES::SignatureKey>>,
+    /// The messages delivered to us
+    receiver: Arc<UnboundedReceiver<Vec<u8>>>,
+    /// Whether or not we are paused
+    is_paused: Arc<AtomicBool>,
+}
+
+impl<TYPES: NodeType> MockPushCdnNetwork<TYPES> {
+    /// Connect a new client with public key `public_key` to `cdn`, subscribed to `topics`.
+    /// Replaces any client already connected with the same public key.
+    pub async fn new(
+        cdn: &Arc<MockPushCdn<TYPES::SignatureKey>>,
+        public_key: TYPES::SignatureKey,
+        topics: Vec<Topic>,
+    ) -> Self {
+        let (sender, receiver) = unbounded();
+        cdn.clients.write().await.insert(
+            public_key.clone(),
+            MockClient {
+                topics: topics.into_iter().collect(),
+                sender,
+            },
+        );
+
+        Self {
+            public_key,
+            cdn: Arc::clone(cdn),
+            receiver: Arc::new(receiver),
+            is_paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Broadcast a message to every client subscribed to `topic`. Recipients are ignored, as
+    /// the CDN routes broadcasts by topic.
+    ///
+    /// # Errors
+    /// If we fail to serialize the message
+    async fn broadcast_message<Ver: StaticVersionType>(
+        &self,
+        message: Message<TYPES>,
+        topic: Topic,
+        _: Ver,
+    ) -> Result<(), NetworkError> {
+        // If we're paused, don't send the message
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let message = Serializer::<Ver>::serialize(&message)
+            .map_err(|e| NetworkError::FailedToSerialize { source: e })?;
+        self.cdn.broadcast(&topic, &message).await;
+
+        Ok(())
+    }
+}
+
+impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for MockPushCdnNetwork<TYPES> {
+    /// Generate n clients connected to one mock CDN. The first `da_committee_size` are
+    /// subscribed to the DA topic.
+    fn generator(
+        _expected_node_count: usize,
+        _num_bootstrap: usize,
+        _network_id: usize,
+        da_committee_size: usize,
+        _is_da: bool,
+        reliability_config: Option<Box<dyn NetworkReliability>>,
+        _secondary_network_delay: Duration,
+    ) -> AsyncGenerator<(Arc<Self>, Arc<Self>)> {
+        if reliability_config.is_some() {
+            warn!("the mock Push CDN ignores the reliability config");
+        }
+
+        let cdn = MockPushCdn::new();
+        Box::pin(move |node_id| {
+            let cdn = Arc::clone(&cdn);
+            Box::pin(async move {
+                let public_key =
+                    TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id).0;
+
+                // Calculate if we're DA or not
+                let topics = if node_id < da_committee_size as u64 {
+                    vec![Topic::DA, Topic::Global]
+                } else {
+                    vec![Topic::Global]
+                };
+
+                let network = Arc::new(Self::new(&cdn, public_key, topics).await);
+                (network.clone(), network)
+            })
+        })
+    }
+
+    /// We don't track in-flight messages, as they are delivered as soon as they are sent
+    fn in_flight_message_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+    for MockPushCdnNetwork<TYPES>
+{
+    /// Pause sending and receiving. While paused, sends are dropped (but still return `Ok(())`)
+    /// and received messages are discarded.
+    fn pause(&self) {
+        self.is_paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume sending and receiving
+    fn resume(&self) {
+        self.is_paused.store(false, Ordering::Relaxed);
+    }
+
+    /// We are connected as soon as we are created, so we don't have to wait.
+    async fn wait_for_ready(&self) {}
+
+    /// We are connected as soon as we are created.
+    async fn is_ready(&self) -> bool {
+        true
+    }
+
+    /// Disconnect from the mock CDN. Subsequent receives return `NetworkError::ShutDown`.
+    fn shut_down<'a, 'b>(&'a self) -> BoxSyncFuture<'b, ()>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        boxed_sync(async move {
+            // Dropping our sender closes our receive channel
+            self.cdn.clients.write().await.remove(&self.public_key);
+        })
+    }
+
+    /// Broadcast a message to every client subscribed to the global topic.
+    ///
+    /// # Errors
+    /// If we fail to serialize the message
+    async fn broadcast_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        _recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        self.broadcast_message(message, Topic::Global, bind_version)
+            .await
+    }
+
+    /// Broadcast a message to every client subscribed to the DA topic.
+    ///
+    /// # Errors
+    /// If we fail to serialize the message
+    async fn da_broadcast_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        _recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        self.broadcast_message(message, Topic::DA, bind_version)
+            .await
+    }
+
+    /// Send a message to the client with public key `recipient`.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message
+    /// - If no client with that public key is connected
+    async fn direct_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipient: TYPES::SignatureKey,
+        _: Ver,
+    ) -> Result<(), NetworkError> {
+        // If we're paused, don't send the message
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let message = Serializer::<Ver>::serialize(&message)
+            .map_err(|e| NetworkError::FailedToSerialize { source: e })?;
+        self.cdn.direct(&recipient, message).await
+    }
+
+    /// Receive a message.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If we fail to deserialize the message
+    async fn recv_msgs(&self) -> Result<Vec<Message<TYPES>>, NetworkError> {
+        let message = self
+            .receiver
+            .recv()
+            .await
+            .map_err(|_| NetworkError::ShutDown)?;
+
+        // If we're paused, receive but don't process messages
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![deserialize_versioned(&message)?])
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    fmt::{Display, Formatter},
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use hotshot_types::traits::network::PushCdnNetworkError;
+use surf_disco::Url;
+
+/// The discovery client brokers and marshals use to find each other. Which run definition
+/// to instantiate them with ([`TestingDef`](super::TestingDef) or
+/// [`ProductionDef`](super::ProductionDef)) can be picked at runtime
+/// from this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiscoveryBackend {
+    /// An embedded (`SQLite`) database at a file path, as used by [`TestingDef`](super::TestingDef)
+    Embedded,
+    /// A Redis server, as used by [`ProductionDef`](super::ProductionDef)
+    Redis,
+}
+
+impl DiscoveryBackend {
+    /// Pick the backend from a discovery endpoint. `redis://` and `rediss://` URLs use Redis;
+    /// anything else is treated as a file path for the embedded database.
+    #[must_use]
+    pub fn from_endpoint(endpoint: &str) -> Self {
+        if endpoint.starts_with("redis://") || endpoint.starts_with("rediss://") {
+            Self::Redis
+        } else {
+            Self::Embedded
+        }
+    }
+}
+
+/// Where brokers and marshals find each other, checked when it is constructed so that a
+/// malformed endpoint is rejected up front rather than deep inside the broker or marshal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiscoveryEndpoint {
+    /// An embedded (`SQLite`) database at this path
+    Sqlite(PathBuf),
+    /// A Redis server at this URL
+    Redis(Url),
+}
+
+impl DiscoveryEndpoint {
+    /// An embedded database at `path`. The file needn't exist yet, but its directory must.
+    ///
+    /// # Errors
+    /// If `path` is a directory, exists but can't be read, or is in a directory that doesn't
+    /// exist
+    pub fn sqlite(path: impl Into<PathBuf>) -> Result<Self, PushCdnNetworkError> {
+        let path = path.into();
+        let invalid = |reason: String| PushCdnNetworkError::InvalidDiscoveryEndpoint {
+            endpoint: path.display().to_string(),
+            reason,
+        };
+
+        if path.as_os_str().is_empty() {
+            return Err(invalid("the path is empty".to_string()));
+        }
+        if path.is_dir() {
+            return Err(invalid("the path is a directory".to_string()));
+        }
+        if path.exists() {
+            File::open(&path)
+                .map_err(|err| invalid(format!("the database is unreadable: {err}")))?;
+        } else {
+            let directory = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            if !directory.is_dir() {
+                return Err(invalid(format!(
+                    "the directory {} does not exist",
+                    directory.display()
+                )));
+            }
+        }
+
+        Ok(Self::Sqlite(path))
+    }
+
+    /// A Redis server at `url`, which must be a `redis://` or `rediss://` URL with a host
+    ///
+    /// # Errors
+    /// If `url` doesn't parse, or isn't such a URL
+    pub fn redis(url: &str) -> Result<Self, PushCdnNetworkError> {
+        let invalid = |reason: String| PushCdnNetworkError::InvalidDiscoveryEndpoint {
+            endpoint: url.to_string(),
+            reason,
+        };
+
+        let parsed = Url::parse(url).map_err(|err| invalid(err.to_string()))?;
+        if !matches!(parsed.scheme(), "redis" | "rediss") {
+            return Err(invalid(format!(
+                "expected a redis:// or rediss:// URL, not {}://",
+                parsed.scheme()
+            )));
+        }
+        if parsed.host_str().unwrap_or_default().is_empty() {
+            return Err(invalid("the URL has no host".to_string()));
+        }
+
+        Ok(Self::Redis(parsed))
+    }
+
+    /// The discovery backend this endpoint is for
+    #[must_use]
+    pub fn backend(&self) -> DiscoveryBackend {
+        match self {
+            Self::Sqlite(_) => DiscoveryBackend::Embedded,
+            Self::Redis(_) => DiscoveryBackend::Redis,
+        }
+    }
+}
+
+impl FromStr for DiscoveryEndpoint {
+    type Err = PushCdnNetworkError;
+
+    /// Parse an endpoint the way the broker and marshal would: `redis://` and `rediss://` URLs
+    /// are Redis servers, and anything else is a path to an embedded database
+    fn from_str(endpoint: &str) -> Result<Self, Self::Err> {
+        match DiscoveryBackend::from_endpoint(endpoint) {
+            DiscoveryBackend::Redis => Self::redis(endpoint),
+            DiscoveryBackend::Embedded => Self::sqlite(endpoint),
+        }
+    }
+}
+
+impl Display for DiscoveryEndpoint {
+    /// The endpoint as the broker and marshal config builders take it
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sqlite(path) => write!(f, "{}", path.display()),
+            Self::Redis(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovery_backend_from_endpoint() {
+        assert_eq!(
+            DiscoveryBackend::from_endpoint("redis://127.0.0.1:6379"),
+            DiscoveryBackend::Redis
+        );
+        assert_eq!(
+            DiscoveryBackend::from_endpoint("rediss://:password@redis.example.com:6380"),
+            DiscoveryBackend::Redis
+        );
+        assert_eq!(
+            DiscoveryBackend::from_endpoint("/tmp/discovery.sqlite"),
+            DiscoveryBackend::Embedded
+        );
+        assert_eq!(
+            DiscoveryBackend::from_endpoint("discovery.sqlite"),
+            DiscoveryBackend::Embedded
+        );
+    }
+
+    #[test]
+    fn discovery_endpoints_are_validated() {
+        let redis: DiscoveryEndpoint = "redis://127.0.0.1:6379".parse().unwrap();
+        assert_eq!(redis.backend(), DiscoveryBackend::Redis);
+        assert_eq!(redis.to_string(), "redis://127.0.0.1:6379");
+
+        let database = std::env::temp_dir().join("discovery.sqlite");
+        let sqlite: DiscoveryEndpoint = database.to_str().unwrap().parse().unwrap();
+        assert_eq!(sqlite, DiscoveryEndpoint::Sqlite(database.clone()));
+        assert_eq!(sqlite.backend(), DiscoveryBackend::Embedded);
+        assert_eq!(sqlite.to_string(), database.display().to_string());
+
+        // Redis URLs without a host
+        for endpoint in ["redis://", "redis:///0"] {
+            assert!(
+                matches!(
+                    endpoint.parse::<DiscoveryEndpoint>(),
+                    Err(PushCdnNetworkError::InvalidDiscoveryEndpoint { .. })
+                ),
+                "accepted {endpoint}"
+            );
+        }
+        assert!(DiscoveryEndpoint::redis("http://127.0.0.1:6379").is_err());
+
+        // A directory, and a file in a directory that doesn't exist
+        assert!(DiscoveryEndpoint::sqlite(std::env::temp_dir()).is_err());
+        assert!(DiscoveryEndpoint::sqlite(
+            std::env::temp_dir().join("no-such-directory/discovery.sqlite")
+        )
+        .is_err());
+        assert!(DiscoveryEndpoint::sqlite("").is_err());
+    }
+}
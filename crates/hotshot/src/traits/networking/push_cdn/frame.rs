@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+
+use cdn_client::reexports::message::{Broadcast, Direct, Message as PushCdnMessage};
+use hotshot_types::{
+    constants::{Version01, VERSION_0_1},
+    message::Message,
+    traits::node_implementation::NodeType,
+};
+use versioned_binary_serialization::{version::Version, BinarySerializer, Serializer};
+
+use super::{MessageOrigin, NetworkError};
+
+/// The prefix of the direct messages we send ourselves to check that we are still connected.
+/// The receive task answers them itself, so they never reach `recv_msgs`.
+pub(super) const PING_PREFIX: &[u8] = b"\xffhotshot-ping";
+
+/// What a frame received from the CDN turned out to be
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ReceivedFrame {
+    /// A consensus message, still framed, and whether it was broadcast or direct
+    Payload(Vec<u8>, MessageOrigin),
+    /// One of our own pings, with its nonce
+    Ping(u64),
+    /// Anything else, such as subscription or sync traffic, which isn't for us
+    Control,
+}
+
+impl ReceivedFrame {
+    /// Work out what `message` is, taking its payload if it has one
+    pub(super) fn classify(message: PushCdnMessage) -> Self {
+        match message {
+            PushCdnMessage::Direct(Direct { message, .. }) => {
+                match message.strip_prefix(PING_PREFIX).map(TryInto::try_into) {
+                    Some(Ok(nonce)) => Self::Ping(u64::from_le_bytes(nonce)),
+                    // A malformed ping is still a ping, not a consensus message
+                    Some(Err(_)) => Self::Control,
+                    None => Self::Payload(message, MessageOrigin::Direct),
+                }
+            }
+            PushCdnMessage::Broadcast(Broadcast { topics, message }) => {
+                Self::Payload(message, MessageOrigin::Broadcast(topics))
+            }
+            _ => Self::Control,
+        }
+    }
+}
+
+/// Deserialize a message, dispatching on the version it was serialized with.
+///
+/// # Errors
+/// - If the version prefix can't be parsed
+/// - If the message was serialized with a version we don't (or don't yet) support
+/// - If the message fails to deserialize
+pub fn deserialize_versioned<TYPES: NodeType>(
+    message: &[u8],
+) -> Result<Message<TYPES>, NetworkError> {
+    let (version, _) =
+        Version::deserialize(message).map_err(|e| NetworkError::FailedToDeserialize {
+            source: e.context("unparseable version prefix"),
+        })?;
+
+    if version == VERSION_0_1 {
+        Serializer::<Version01>::deserialize(message)
+            .map_err(|e| NetworkError::FailedToDeserialize { source: e })
+    } else if (version.major, version.minor) > (VERSION_0_1.major, VERSION_0_1.minor) {
+        // Newer than anything we know how to read; a peer has likely upgraded before us
+        Err(NetworkError::FailedToDeserialize {
+            source: anyhow::format_err!(
+                "unsupported future version {version}, latest supported is {VERSION_0_1}"
+            ),
+        })
+    } else {
+        Err(NetworkError::FailedToDeserialize {
+            source: anyhow::format_err!("unknown version {version}"),
+        })
+    }
+}
+
+/// The version `message` was serialized with, if it is newer than any we can read
+pub(super) fn future_version(message: &[u8]) -> Option<Version> {
+    let (version, _) = Version::deserialize(message).ok()?;
+    ((version.major, version.minor) > (VERSION_0_1.major, VERSION_0_1.minor)).then_some(version)
+}
+
+/// The first byte of a compressed frame. Uncompressed frames start with the little-endian
+/// major version of the message, which is nowhere near this.
+pub(super) const COMPRESSED_FRAME_FLAG: u8 = 0xc7;
+
+/// Frame a serialized message for the wire: either as-is, or, if `compress` is set,
+/// `COMPRESSED_FRAME_FLAG` followed by the LZ4-compressed message (prefixed with its
+/// uncompressed size).
+pub(super) fn encode_frame(serialized: Vec<u8>, compress: bool) -> Vec<u8> {
+    if !compress {
+        return serialized;
+    }
+
+    let mut frame = vec![COMPRESSED_FRAME_FLAG];
+    frame.extend_from_slice(&lz4_flex::compress_prepend_size(&serialized));
+    frame
+}
+
+/// Unwrap a frame made by [`encode_frame`], decompressing it if needed, regardless of whether
+/// we compress our own messages.
+///
+/// # Errors
+/// - If the frame is compressed and claims to decompress to more than `max_size` bytes
+/// - If the frame is compressed and fails to decompress
+pub fn decode_frame(frame: &[u8], max_size: usize) -> Result<Cow<'_, [u8]>, NetworkError> {
+    let Some((&COMPRESSED_FRAME_FLAG, compressed)) = frame.split_first() else {
+        return Ok(Cow::Borrowed(frame));
+    };
+
+    // Check the claimed size before decompressing allocates for it
+    let size = compressed
+        .get(..4)
+        .and_then(|size| size.try_into().ok())
+        .map(|size| u32::from_le_bytes(size) as usize);
+    match size {
+        None => Err(NetworkError::FailedToDeserialize {
+            source: anyhow::format_err!("compressed frame is truncated"),
+        }),
+        Some(size) if size > max_size => Err(NetworkError::MessageTooLarge {
+            size,
+            limit: max_size,
+        }),
+        Some(_) => lz4_flex::decompress_size_prepended(compressed)
+            .map(Cow::Owned)
+            .map_err(|err| NetworkError::FailedToDeserialize {
+                source: anyhow::Error::new(err).context("failed to decompress frame"),
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdn_client::reexports::message::Topic;
+
+    #[test]
+    fn frames_round_trip() {
+        let message = b"hello, hello, hello, hello, world".to_vec();
+
+        // Uncompressed frames are the message itself
+        let frame = encode_frame(message.clone(), false);
+        assert_eq!(frame, message);
+        assert_eq!(decode_frame(&frame, 1024).unwrap(), message.as_slice());
+
+        // Compressed frames are flagged, and decompress to the message
+        let frame = encode_frame(message.clone(), true);
+        assert_eq!(frame[0], COMPRESSED_FRAME_FLAG);
+        assert_eq!(decode_frame(&frame, 1024).unwrap(), message.as_slice());
+
+        // Unless they claim to decompress to more than we allow
+        assert!(matches!(
+            decode_frame(&frame, message.len() - 1),
+            Err(NetworkError::MessageTooLarge { .. })
+        ));
+
+        // Or are cut short
+        assert!(decode_frame(&frame[..3], 1024).is_err());
+    }
+
+    #[test]
+    fn only_consensus_frames_are_forwarded() {
+        let ping = [PING_PREFIX, &7u64.to_le_bytes()].concat();
+        let frames = vec![
+            PushCdnMessage::Subscribe(vec![Topic::DA]),
+            PushCdnMessage::Direct(Direct {
+                recipient: Vec::new(),
+                message: b"direct".to_vec(),
+            }),
+            PushCdnMessage::Unsubscribe(vec![Topic::DA]),
+            PushCdnMessage::Direct(Direct {
+                recipient: Vec::new(),
+                message: ping,
+            }),
+            PushCdnMessage::Direct(Direct {
+                recipient: Vec::new(),
+                message: PING_PREFIX.to_vec(),
+            }),
+            PushCdnMessage::Broadcast(Broadcast {
+                topics: vec![Topic::Global],
+                message: b"broadcast".to_vec(),
+            }),
+        ];
+
+        assert_eq!(
+            frames
+                .into_iter()
+                .map(ReceivedFrame::classify)
+                .collect::<Vec<_>>(),
+            vec![
+                ReceivedFrame::Control,
+                ReceivedFrame::Payload(b"direct".to_vec(), MessageOrigin::Direct),
+                ReceivedFrame::Control,
+                ReceivedFrame::Ping(7),
+                ReceivedFrame::Control,
+                ReceivedFrame::Payload(
+                    b"broadcast".to_vec(),
+                    MessageOrigin::Broadcast(vec![Topic::Global])
+                ),
+            ]
+        );
+    }
+}
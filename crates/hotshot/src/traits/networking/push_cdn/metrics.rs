@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use hotshot_types::traits::metrics::{Counter, Gauge, Histogram, Metrics, NoMetrics};
+
+use super::Topic;
+
+/// Metrics for the Push CDN network
+#[derive(Clone, Debug)]
+pub(super) struct PushCdnMetrics {
+    /// A [`Counter`] which tracks how many broadcast messages have been sent
+    pub(super) broadcasts_sent: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many direct messages have been sent
+    pub(super) direct_messages_sent: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many messages have been received
+    pub(super) receive_successes: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many messages we failed to receive
+    pub(super) receive_errors: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many received messages we dropped for being over our
+    /// inbound rate limit
+    pub(super) receive_rate_limited: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many received messages we skipped because they were
+    /// serialized with a version newer than any we support
+    pub(super) receive_future_version: Box<dyn Counter>,
+    /// A [`Histogram`] of how long it takes to serialize outgoing messages, in seconds
+    pub(super) serialize_duration: Box<dyn Histogram>,
+    /// A [`Counter`] which tracks how many times we lost our connection to the CDN
+    pub(super) disconnects: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many times we regained our connection to the CDN
+    pub(super) reconnects: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many times we authenticated with the marshal again after
+    /// it rejected us
+    pub(super) reauthentications: Box<dyn Counter>,
+    /// Metrics for the global topic
+    global_topic: TopicMetrics,
+    /// Metrics for the DA topic
+    da_topic: TopicMetrics,
+}
+
+impl PushCdnMetrics {
+    /// Create a new instance of this [`PushCdnMetrics`] struct, setting all the counters and
+    /// histograms
+    #[must_use]
+    pub(super) fn new(metrics: &dyn Metrics) -> Self {
+        let metrics = metrics.subgroup(String::from("push_cdn"));
+        Self {
+            broadcasts_sent: metrics.create_counter(String::from("broadcasts_sent"), None),
+            direct_messages_sent: metrics
+                .create_counter(String::from("direct_messages_sent"), None),
+            receive_successes: metrics.create_counter(String::from("receive_successes"), None),
+            receive_errors: metrics.create_counter(String::from("receive_errors"), None),
+            receive_rate_limited: metrics
+                .create_counter(String::from("receive_rate_limited"), None),
+            receive_future_version: metrics
+                .create_counter(String::from("receive_future_version"), None),
+            serialize_duration: metrics.create_histogram(
+                String::from("serialize_duration"),
+                Some(String::from("seconds")),
+            ),
+            disconnects: metrics.create_counter(String::from("disconnects"), None),
+            reconnects: metrics.create_counter(String::from("reconnects"), None),
+            reauthentications: metrics.create_counter(String::from("reauthentications"), None),
+            global_topic: TopicMetrics::new(&*metrics, "global"),
+            da_topic: TopicMetrics::new(&*metrics, "da"),
+        }
+    }
+
+    /// Get the metrics for `topic`
+    pub(super) fn topic(&self, topic: &Topic) -> &TopicMetrics {
+        match topic {
+            Topic::Global => &self.global_topic,
+            Topic::DA => &self.da_topic,
+        }
+    }
+}
+
+/// Metrics for a single Push CDN topic, in their own subgroup
+#[derive(Clone, Debug)]
+pub(super) struct TopicMetrics {
+    /// A [`Counter`] which tracks how many messages have been broadcast to the topic
+    pub(super) broadcasts_sent: Box<dyn Counter>,
+    /// A [`Gauge`] which is 1 while we are subscribed to the topic, and 0 otherwise
+    pub(super) subscribed: Box<dyn Gauge>,
+}
+
+impl TopicMetrics {
+    /// Create the metrics for the topic called `name`
+    fn new(metrics: &dyn Metrics, name: &str) -> Self {
+        let metrics = metrics.subgroup(format!("topic_{name}"));
+        Self {
+            broadcasts_sent: metrics.create_counter(String::from("broadcasts_sent"), None),
+            subscribed: metrics.create_gauge(String::from("subscribed"), None),
+        }
+    }
+}
+
+impl Default for PushCdnMetrics {
+    fn default() -> Self {
+        Self::new(&*NoMetrics::boxed())
+    }
+}
+
+/// How many bytes we have put on the wire, for callers budgeting bandwidth. Counts framed
+/// messages, after any compression, once per successful send.
+#[derive(Debug, Default)]
+pub(super) struct BytesSent {
+    /// Every byte we sent
+    pub(super) total: AtomicU64,
+    /// Bytes sent in direct messages
+    direct: AtomicU64,
+    /// Bytes broadcast to the global topic
+    global_topic: AtomicU64,
+    /// Bytes broadcast to the DA topic
+    da_topic: AtomicU64,
+}
+
+impl BytesSent {
+    /// The counter for bytes broadcast to `topic`
+    pub(super) fn topic(&self, topic: &Topic) -> &AtomicU64 {
+        match topic {
+            Topic::Global => &self.global_topic,
+            Topic::DA => &self.da_topic,
+        }
+    }
+
+    /// Count a successful broadcast of `bytes` to `topics`. A broadcast to several topics goes
+    /// on the wire once, so it counts toward each topic but only once toward the total.
+    pub(super) fn broadcast(&self, topics: &[Topic], bytes: usize) {
+        let bytes = bytes as u64;
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+        for topic in topics {
+            self.topic(topic).fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a successful direct message of `bytes`
+    pub(super) fn direct(&self, bytes: usize) {
+        let bytes = bytes as u64;
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+        self.direct.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,2781 @@
+/// Where brokers and marshals find each other
+mod discovery;
+/// How messages are framed on the wire, and what the frames we receive turn out to be
+mod frame;
+/// Metrics for the Push CDN network, and the bytes we have sent
+mod metrics;
+/// How fast we accept inbound messages
+mod rate_limit;
+/// The queue outgoing sends wait in, so that urgent messages overtake bulk ones
+mod send_queue;
+
+pub use self::{
+    discovery::{DiscoveryBackend, DiscoveryEndpoint},
+    frame::{decode_frame, deserialize_versioned},
+    rate_limit::{InboundRateLimit, RateLimit},
+    send_queue::{SendPriority, SendQueueConfig},
+};
+
+use self::{
+    frame::{encode_frame, future_version, ReceivedFrame, PING_PREFIX},
+    metrics::{BytesSent, PushCdnMetrics},
+    rate_limit::InboundRateLimiter,
+    send_queue::SendQueue,
+};
+
+use super::NetworkError;
+use anyhow::{bail, ensure, Context};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use async_compatibility_layer::art::{async_sleep, async_spawn, async_timeout};
+use async_compatibility_layer::channel::{
+    unbounded, UnboundedReceiver, UnboundedSendError, UnboundedSender,
+};
+use async_lock::RwLock;
+#[cfg(feature = "hotshot-testing")]
+use async_lock::{Mutex, OnceCell, RwLockWriteGuard};
+#[cfg(async_executor_impl = "async-std")]
+use async_std::task::JoinHandle;
+use async_trait::async_trait;
+use bincode::config::Options;
+use cdn_broker::reexports::connection::protocols::Tcp;
+use cdn_broker::reexports::def::RunDef;
+use cdn_broker::reexports::discovery::{Embedded, Redis};
+#[cfg(feature = "hotshot-testing")]
+use cdn_broker::{Broker, Config, ConfigBuilder as BrokerConfigBuilder};
+pub use cdn_client::reexports::crypto::signature::KeyPair;
+pub use cdn_client::reexports::message::Topic;
+use cdn_client::{
+    reexports::{
+        connection::protocols::Quic,
+        crypto::signature::{Serializable, SignatureScheme},
+        error::Error as CdnError,
+    },
+    Client, ConfigBuilder as ClientConfigBuilder,
+};
+#[cfg(feature = "hotshot-testing")]
+use cdn_marshal::{ConfigBuilder as MarshalConfigBuilder, Marshal};
+use futures::{
+    channel::{mpsc, oneshot},
+    future::join_all,
+    stream::{self, Stream},
+};
+use hotshot_task_impls::helpers::cancel_task;
+use hotshot_types::traits::network::AsyncGenerator;
+#[cfg(feature = "hotshot-testing")]
+use hotshot_types::traits::network::{NetworkReliability, TestableNetworkingImplementation};
+use hotshot_types::{
+    boxed_sync,
+    constants::VERSION_0_1,
+    data::ViewNumber,
+    message::{Message, MessagePurpose},
+    traits::{
+        metrics::{Counter, Metrics},
+        network::{ConnectedNetwork, ConsensusIntentEvent, PushCdnNetworkError, ViewMessage},
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+    },
+    utils::bincode_opts,
+    BoxSyncFuture,
+};
+#[cfg(feature = "hotshot-testing")]
+use rand::rngs::StdRng;
+#[cfg(feature = "hotshot-testing")]
+use rand::{seq::SliceRandom, RngCore, SeedableRng};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, VecDeque};
+use std::future::Future;
+use std::marker::PhantomData;
+#[cfg(feature = "hotshot-testing")]
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex as StdMutex, PoisonError,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+#[cfg(async_executor_impl = "tokio")]
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use versioned_binary_serialization::{
+    version::{StaticVersionType, Version},
+    BinarySerializer, Serializer,
+};
+
+/// The version byte that prefixes canonically encoded signatures. Signatures without it use
+/// the legacy `bincode` encoding.
+const CANONICAL_SIGNATURE_VERSION: u8 = 1;
+
+/// The largest encoded signature we will try to decode, in bytes. Signatures arrive from
+/// unauthenticated peers, so anything larger is rejected before it is deserialized. This is
+/// comfortably above the size of any signature scheme we use (a BLS signature is under 100
+/// bytes in either encoding).
+const MAX_SIGNATURE_SIZE: usize = 1024;
+
+/// Canonically encode a signature. The layout is:
+///
+/// | bytes   | contents                                                |
+/// |---------|---------------------------------------------------------|
+/// | 0       | `CANONICAL_SIGNATURE_VERSION`                           |
+/// | 1..5    | the length `n` of the signature, as a little-endian u32 |
+/// | 5..5+n  | the compressed `CanonicalSerialize` signature           |
+///
+/// # Errors
+/// If the signature fails to serialize or is too large
+fn encode_signature<S: CanonicalSerialize>(signature: &S) -> anyhow::Result<Vec<u8>> {
+    let length = u32::try_from(signature.compressed_size()).context("signature too large")?;
+
+    let mut encoded = Vec::with_capacity(5 + length as usize);
+    encoded.push(CANONICAL_SIGNATURE_VERSION);
+    encoded.extend_from_slice(&length.to_le_bytes());
+    signature
+        .serialize_compressed(&mut encoded)
+        .context("failed to serialize signature")?;
+
+    Ok(encoded)
+}
+
+/// Decode a signature encoded with [`encode_signature`].
+///
+/// # Errors
+/// If the version byte or length prefix is wrong, or the signature fails to deserialize
+fn decode_signature<S: CanonicalDeserialize>(encoded: &[u8]) -> anyhow::Result<S> {
+    let Some((&version, rest)) = encoded.split_first() else {
+        bail!("signature is empty");
+    };
+    ensure!(
+        version == CANONICAL_SIGNATURE_VERSION,
+        "unknown signature version {version}"
+    );
+
+    ensure!(rest.len() >= 4, "signature length prefix is truncated");
+    let (length, signature) = rest.split_at(4);
+    let length = u32::from_le_bytes(length.try_into()?) as usize;
+    ensure!(
+        length <= MAX_SIGNATURE_SIZE,
+        "signature length {length} exceeds the maximum of {MAX_SIGNATURE_SIZE}"
+    );
+    ensure!(
+        signature.len() == length,
+        "signature length mismatch, expected {length}, got {}",
+        signature.len()
+    );
+
+    S::deserialize_compressed(signature).context("failed to deserialize signature")
+}
+
+/// A wrapped `SignatureKey`. We need to implement the Push CDN's `SignatureScheme`
+/// trait in order to sign and verify messages to/from the CDN.
+#[derive(Clone, Eq, PartialEq)]
+pub struct WrappedSignatureKey<T: SignatureKey + 'static>(pub T);
+impl<T: SignatureKey> SignatureScheme for WrappedSignatureKey<T> {
+    type PrivateKey = T::PrivateKey;
+    type PublicKey = Self;
+
+    /// Sign a message of arbitrary data and return the canonically encoded signature
+    fn sign(private_key: &Self::PrivateKey, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let signature = T::sign(private_key, message)?;
+        encode_signature(&signature)
+    }
+
+    /// Verify a message of arbitrary data and return the result. Accepts canonically encoded
+    /// signatures and, while nodes migrate, legacy `bincode` ones. Signatures larger than
+    /// `MAX_SIGNATURE_SIZE` are rejected without being decoded.
+    fn verify(public_key: &Self::PublicKey, message: &[u8], signature: &[u8]) -> bool {
+        if signature.len() > MAX_SIGNATURE_SIZE {
+            debug!(
+                "rejecting signature of {} bytes, the maximum is {MAX_SIGNATURE_SIZE}",
+                signature.len()
+            );
+            return false;
+        }
+
+        let signature: T::PureAssembledSignatureType = match decode_signature(signature) {
+            Ok(signature) => signature,
+            // TODO: remove once every node signs with the canonical encoding
+            // The limit stops a forged length prefix from making `bincode` allocate for it
+            Err(_) => match bincode_opts()
+                .with_limit(MAX_SIGNATURE_SIZE as u64)
+                .deserialize(signature)
+            {
+                Ok(signature) => signature,
+                Err(err) => {
+                    debug!("failed to decode signature: {err}");
+                    return false;
+                }
+            },
+        };
+
+        public_key.0.validate(&signature, message)
+    }
+}
+
+/// We need to implement the `Serializable` so the Push CDN can serialize the signatures
+/// and public keys and send them over the wire.
+impl<T: SignatureKey> Serializable for WrappedSignatureKey<T> {
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.0.to_bytes())
+    }
+
+    fn deserialize(serialized: &[u8]) -> anyhow::Result<Self> {
+        Ok(WrappedSignatureKey(T::from_bytes(serialized)?))
+    }
+}
+
+/// The testing run definition for the Push CDN.
+/// Uses the real protocols, but with an embedded discovery client.
+pub struct TestingDef<TYPES: NodeType> {
+    /// Phantom data to hold the type
+    pd: PhantomData<TYPES>,
+}
+
+impl<TYPES: NodeType> RunDef for TestingDef<TYPES> {
+    type BrokerScheme = WrappedSignatureKey<TYPES::SignatureKey>;
+    type BrokerProtocol = Tcp;
+
+    type UserScheme = WrappedSignatureKey<TYPES::SignatureKey>;
+    type UserProtocol = Quic;
+
+    type DiscoveryClientType = Embedded;
+}
+
+/// The production run definition for the Push CDN.
+/// Uses the real protocols and a Redis discovery client.
+pub struct ProductionDef<TYPES: NodeType> {
+    /// Phantom data to hold the type
+    pd: PhantomData<TYPES>,
+}
+
+impl<TYPES: NodeType> RunDef for ProductionDef<TYPES> {
+    type BrokerScheme = WrappedSignatureKey<TYPES::SignatureKey>;
+    type BrokerProtocol = Tcp;
+
+    type UserScheme = WrappedSignatureKey<TYPES::SignatureKey>;
+    type UserProtocol = Quic;
+
+    type DiscoveryClientType = Redis;
+}
+
+/// Every topic the Push CDN knows about
+pub const KNOWN_TOPICS: [Topic; 2] = [Topic::Global, Topic::DA];
+
+/// Parse a topic from its name, as given to [`PushCdnNetwork::new`]
+///
+/// # Errors
+/// `PushCdnNetworkError::UnknownTopic` if `topic` doesn't name one of [`KNOWN_TOPICS`]
+pub fn parse_topic(topic: &str) -> Result<Topic, PushCdnNetworkError> {
+    Topic::try_from(topic.to_string()).map_err(|_| PushCdnNetworkError::UnknownTopic {
+        topic: topic.to_string(),
+        known: KNOWN_TOPICS
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    })
+}
+
+/// How failed operations (sends and reconnections) are retried before giving up on them
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts (including the first) before giving up
+    pub max_attempts: u32,
+    /// The delay before the first retry
+    pub base_delay: Duration,
+    /// The factor the delay is multiplied by for each subsequent retry
+    pub multiplier: u32,
+    /// The maximum random delay added to each retry, so nodes don't retry in lockstep
+    pub jitter: Duration,
+    /// How long a single attempt may take before it is abandoned
+    pub attempt_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+            multiplier: 2,
+            jitter: Duration::from_millis(10),
+            attempt_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default policy for reconnecting to the marshal after we fail to receive. More patient
+    /// than the default send policy, so that a broker restart is ridden out.
+    #[must_use]
+    pub fn default_reconnect() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(250),
+            multiplier: 2,
+            jitter: Duration::from_millis(50),
+            attempt_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Whether a send that failed with `error` is worth retrying
+    fn is_retryable(error: &NetworkError) -> bool {
+        matches!(
+            error,
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::ConnectionLost { .. }
+                    | PushCdnNetworkError::SendTimeout
+            }
+        )
+    }
+
+    /// Whether an operation that failed with `error` needs us to authenticate with the marshal
+    /// again (and so get a fresh broker) before it is retried
+    fn needs_reauthentication(error: &NetworkError) -> bool {
+        matches!(
+            error,
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::AuthenticationFailed { .. }
+            }
+        )
+    }
+
+    /// The delay before the `retry`th retry (starting from 0)
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(self.multiplier.saturating_pow(retry));
+
+        backoff + self.jitter.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// How we keep our connection to the CDN alive, and how quickly we notice that it has silently
+/// died.
+///
+/// None of these reach the QUIC layer: the CDN client doesn't expose its transport config, so
+/// keepalives are pings we send ourselves through our broker (see
+/// [`PushCdnNetwork::ping`]), and 0-RTT resumption isn't available.
+#[derive(Clone, Debug)]
+pub struct ConnectionTuning {
+    /// How often to ping ourselves while connected, if at all. Without keepalives, a silent
+    /// broker is only noticed when a send or receive fails.
+    pub keepalive_interval: Option<Duration>,
+    /// How long a keepalive may go unanswered before we consider the connection lost
+    pub max_idle_timeout: Duration,
+}
+
+impl Default for ConnectionTuning {
+    fn default() -> Self {
+        Self {
+            keepalive_interval: None,
+            max_idle_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// How often we warn about receiving messages with each unsupported future version
+const FUTURE_VERSION_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// For each unsupported future version (by major and minor) we have received messages with,
+/// when we last warned about it and how many messages with it we have skipped since
+type FutureVersionWarnings = BTreeMap<(u16, u16), (Instant, u64)>;
+
+/// By default, broadcasts always go to the whole topic, whoever they are meant for. Callers
+/// opt in to sending small broadcasts directly with
+/// [`PushCdnNetwork::with_direct_fanout_threshold`].
+pub const DEFAULT_DIRECT_FANOUT_THRESHOLD: usize = 0;
+
+/// By default, we refuse to send serialized messages larger than this many bytes (64 MiB)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The most queued node lookups we hold on to. Beyond this, the oldest are dropped.
+pub const MAX_QUEUED_LOOKUPS: usize = 128;
+
+/// How long shutting down waits for in-flight sends to resolve before closing the client
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often `flush` checks whether the in-flight sends have resolved
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The underlying Push CDN client type
+pub type PushCdnClient<TYPES> =
+    Client<WrappedSignatureKey<<TYPES as NodeType>::SignatureKey>, Quic>;
+
+/// The pings we are waiting on, by nonce. The CDN doesn't tell us who sent a direct message,
+/// so anyone can send us something that looks like a ping. Nonces are random, and only a
+/// ping whose nonce we are waiting on counts; anything else is dropped.
+#[derive(Default)]
+struct OutstandingPings {
+    /// Who to tell when the ping with each nonce comes back
+    waiting: StdMutex<BTreeMap<u64, oneshot::Sender<()>>>,
+}
+
+impl OutstandingPings {
+    /// Start waiting on a ping with a fresh nonce. We stop waiting when the returned ping is
+    /// dropped, so there are only ever as many entries as pings in progress.
+    fn start(&self) -> PendingPing<'_> {
+        let (answer, answered) = oneshot::channel();
+        let mut waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        let nonce = loop {
+            let nonce = rand::random::<u64>();
+            if let Entry::Vacant(entry) = waiting.entry(nonce) {
+                entry.insert(answer);
+                break nonce;
+            }
+        };
+
+        PendingPing {
+            pings: self,
+            nonce,
+            answered,
+        }
+    }
+
+    /// Answer the ping with `nonce`, if we are waiting on it. Returns whether we were.
+    fn answer(&self, nonce: u64) -> bool {
+        self.waiting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&nonce)
+            .is_some_and(|answer| answer.send(()).is_ok())
+    }
+}
+
+/// A ping we are waiting on, which we stop waiting on when dropped
+struct PendingPing<'a> {
+    /// Where we are waiting on it
+    pings: &'a OutstandingPings,
+    /// Its nonce
+    nonce: u64,
+    /// Resolves when it comes back
+    answered: oneshot::Receiver<()>,
+}
+
+impl Drop for PendingPing<'_> {
+    fn drop(&mut self) {
+        self.pings
+            .waiting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.nonce);
+    }
+}
+
+/// A change in whether we are connected to the CDN, as reported by
+/// [`PushCdnNetwork::connection_events`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// We regained our connection, by reconnecting or because a send, receive, or ping
+    /// succeeded
+    Connected,
+    /// We lost our connection: a send, receive, or ping failed because of it, or we shut down
+    Disconnected,
+}
+
+/// How a received message reached us, as reported by [`PushCdnNetwork::recv_msgs_with_meta`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageOrigin {
+    /// It was broadcast to these topics. A message broadcast to several topics at once
+    /// arrives once, with all of them.
+    Broadcast(Vec<Topic>),
+    /// It was sent directly to us
+    Direct,
+}
+
+/// Whether we are connected to the CDN, and who to tell when that changes
+struct ConnectionState {
+    /// Whether we were connected the last time we found out
+    connected: AtomicBool,
+    /// Everyone listening for changes. Listeners that have gone away are dropped on the next
+    /// change.
+    listeners: StdMutex<Vec<mpsc::UnboundedSender<ConnectionEvent>>>,
+    /// Counts how many times we lost the connection
+    disconnects: Box<dyn Counter>,
+    /// Counts how many times we regained the connection
+    reconnects: Box<dyn Counter>,
+}
+
+impl ConnectionState {
+    /// Start out connected, counting changes in `metrics`
+    fn new(metrics: &PushCdnMetrics) -> Self {
+        Self {
+            connected: AtomicBool::new(true),
+            listeners: StdMutex::new(Vec::new()),
+            disconnects: metrics.disconnects.clone(),
+            reconnects: metrics.reconnects.clone(),
+        }
+    }
+
+    /// Whether we were connected the last time we found out
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Record whether we are connected. If that changed, log it, count it, and tell our
+    /// listeners.
+    fn set_connected(&self, connected: bool) {
+        if self.connected.swap(connected, Ordering::Relaxed) == connected {
+            return;
+        }
+
+        let event = if connected {
+            info!("connection to the CDN restored");
+            self.reconnects.add(1);
+            ConnectionEvent::Connected
+        } else {
+            warn!("lost connection to the CDN");
+            self.disconnects.add(1);
+            ConnectionEvent::Disconnected
+        };
+
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|listener| listener.unbounded_send(event).is_ok());
+    }
+
+    /// Start listening for changes
+    fn listen(&self) -> mpsc::UnboundedReceiver<ConnectionEvent> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(sender);
+        receiver
+    }
+}
+
+/// The (still framed) payload of a consensus message and how it reached us, or the error we
+/// got trying to receive one, forwarded by the receive task
+type ReceivedMessage = Result<(Vec<u8>, MessageOrigin), NetworkError>;
+
+/// Everything we need to (re)connect to the marshal
+struct ConnectionDetails<TYPES: NodeType> {
+    /// The endpoint of the marshal
+    marshal_endpoint: String,
+    /// Our public key
+    public_key: TYPES::SignatureKey,
+    /// Our private key, which we use to authenticate with the marshal
+    private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
+}
+
+impl<TYPES: NodeType> ConnectionDetails<TYPES> {
+    /// Create a new client, connected to the marshal and subscribed to `topics`.
+    ///
+    /// # Errors
+    /// If we fail to build the client config or to connect
+    async fn connect(&self, topics: Vec<Topic>) -> anyhow::Result<PushCdnClient<TYPES>> {
+        // Build config
+        let config = ClientConfigBuilder::default()
+            .endpoint(self.marshal_endpoint.clone())
+            .subscribed_topics(topics)
+            .keypair(KeyPair {
+                public_key: WrappedSignatureKey(self.public_key.clone()),
+                private_key: self.private_key.clone(),
+            })
+            .build()?;
+
+        // Create the client, performing the connection
+        Ok(Client::new(config).await?)
+    }
+}
+
+/// Map an error from the Push CDN client while sending into a [`NetworkError`], so that callers
+/// can tell transient failures from permanent ones, and broker failures from marshal ones.
+fn classify_send_error(err: CdnError) -> NetworkError {
+    match err {
+        CdnError::Connection(reason) => NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::ConnectionLost { reason },
+        },
+        CdnError::Authentication(reason) => NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::AuthenticationFailed { reason },
+        },
+        err => {
+            warn!("unexpected error sending message: {err}");
+            NetworkError::CouldNotDeliver
+        }
+    }
+}
+
+/// Wrap an error we got serializing a message, noting what kind of message it was and for
+/// which view.
+fn serialization_error(purpose: MessagePurpose, view: u64, error: anyhow::Error) -> NetworkError {
+    let context = format!("failed to serialize {purpose:?} message for view {view}");
+    warn!("{context}: {error}");
+
+    NetworkError::FailedToSerialize {
+        source: error.context(context),
+    }
+}
+
+/// A communication channel to the Push CDN, which is a collection of brokers and a marshal
+/// that helps organize them all.
+#[derive(Clone)]
+/// Is generic over both the type of key and the network protocol.
+pub struct PushCdnNetwork<TYPES: NodeType> {
+    /// The underlying client. Taken (and thereby closed) when we are shut down.
+    client: Arc<RwLock<Option<PushCdnClient<TYPES>>>>,
+    /// The channel fed by the receive task. If it closes, the receive task has died
+    /// and the network needs to be rebuilt.
+    receiver: Arc<UnboundedReceiver<ReceivedMessage>>,
+    /// The task that pulls messages off of the client and forwards them to `receiver`
+    receive_task: Arc<RwLock<Option<JoinHandle<()>>>>,
+    /// The task that sends keepalive pings, if we have a keepalive interval
+    keepalive_task: Arc<StdMutex<Option<JoinHandle<()>>>>,
+    /// The queue our sends wait in, if we queue them rather than sending each inline
+    send_queue: Option<Arc<SendQueue>>,
+    /// Tells the receive task to carry on after it has forwarded an error, once we have
+    /// tried to reconnect
+    resume_receiving: UnboundedSender<()>,
+    /// How we reach the marshal when we need to reconnect
+    connection: Arc<ConnectionDetails<TYPES>>,
+    /// Whether we were connected the last time we found out, by sending, receiving, or
+    /// pinging, and who to tell when that changes
+    connection_state: Arc<ConnectionState>,
+    /// The pings we are waiting on, which the receive task answers as they come back
+    outstanding_pings: Arc<OutstandingPings>,
+    /// How we retry failed sends
+    retry_policy: RetryPolicy,
+    /// How we retry reconnecting after we fail to receive
+    reconnect_policy: RetryPolicy,
+    /// The most messages `recv_msgs` returns at once
+    max_batch: usize,
+    /// How long `recv_msgs` waits for a message before returning nothing, if at all
+    receive_timeout: Option<Duration>,
+    /// Broadcasts to fewer than this many (explicit) recipients are sent directly to each of
+    /// them instead of to the whole topic
+    direct_fanout_threshold: usize,
+    /// The largest serialized message we will send (or decompress), in bytes
+    max_message_size: usize,
+    /// Whether we compress the messages we send
+    compress: bool,
+    /// Our send and receive metrics
+    metrics: Arc<PushCdnMetrics>,
+    /// The topics we are currently subscribed to
+    subscriptions: Arc<RwLock<BTreeSet<Topic>>>,
+    /// The node lookups queued with us, oldest first. We don't look nodes up ourselves, but a
+    /// combined network may forward these to a network that does.
+    queued_lookups: Arc<StdMutex<VecDeque<(ViewNumber, TYPES::SignatureKey)>>>,
+    /// Whether or not the underlying network is supposed to be paused
+    is_paused: Arc<AtomicBool>,
+    /// The number of sends that have been dispatched but have not yet resolved
+    in_flight_message_count: Arc<AtomicUsize>,
+    /// How many bytes we have sent, in total, directly, and to each topic
+    bytes_sent: Arc<BytesSent>,
+    /// When we last warned about each unsupported future version we have received
+    future_version_warnings: Arc<StdMutex<FutureVersionWarnings>>,
+    /// How fast we accept inbound messages, if we limit it
+    inbound_rate_limiter: Option<Arc<StdMutex<InboundRateLimiter>>>,
+    /// Config to introduce unreliability to outgoing messages
+    #[cfg(feature = "hotshot-testing")]
+    reliability_config: Option<Arc<dyn NetworkReliability>>,
+    /// How long to wait before each outgoing send
+    #[cfg(feature = "hotshot-testing")]
+    send_delay: Duration,
+    /// Puts received messages in a deterministic order, if set
+    #[cfg(feature = "hotshot-testing")]
+    receive_ordering: Option<Arc<Mutex<OrderedReceiver<TYPES>>>>,
+}
+
+impl<TYPES: NodeType> PushCdnNetwork<TYPES> {
+    /// Create a new `PushCdnNetwork` (really a client) from a marshal endpoint, a list of initial
+    /// topics we are interested in, and our wrapped keypair that we use to authenticate with the
+    /// marshal. Records send and receive metrics to `metrics`, if provided.
+    ///
+    /// # Errors
+    /// - `PushCdnNetworkError::UnknownTopic` if one of `topics` isn't a topic we know
+    /// - If we fail the initial connection
+    pub async fn new(
+        marshal_endpoint: String,
+        topics: Vec<String>,
+        keypair: KeyPair<WrappedSignatureKey<TYPES::SignatureKey>>,
+        metrics: Option<Arc<dyn Metrics>>,
+    ) -> anyhow::Result<Self> {
+        // Transform topics to our internal representation
+        let computed_topics = topics
+            .iter()
+            .map(|topic| parse_topic(topic))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let KeyPair {
+            public_key: WrappedSignatureKey(public_key),
+            private_key,
+        } = keypair;
+        let connection = ConnectionDetails {
+            marshal_endpoint,
+            public_key,
+            private_key,
+        };
+
+        let metrics = metrics.map_or_else(PushCdnMetrics::default, |metrics| {
+            PushCdnMetrics::new(&*metrics)
+        });
+
+        // Perform the initial connection
+        Self::connect(connection, computed_topics, metrics).await
+    }
+
+    /// Create a new `PushCdnNetwork` like [`PushCdnNetwork::new`], but with the keypair derived
+    /// from `seed` and `index` the same way the test generator and the example infra derive
+    /// them, so that nodes can be brought up reproducibly from their index alone.
+    ///
+    /// # Errors
+    /// If we fail the initial connection
+    pub async fn new_from_seed(
+        marshal_endpoint: String,
+        topics: Vec<String>,
+        seed: [u8; 32],
+        index: u64,
+        metrics: Option<Arc<dyn Metrics>>,
+    ) -> anyhow::Result<Self> {
+        let (public_key, private_key) =
+            TYPES::SignatureKey::generated_from_seed_indexed(seed, index);
+        let keypair = KeyPair {
+            public_key: WrappedSignatureKey(public_key),
+            private_key,
+        };
+
+        Self::new(marshal_endpoint, topics, keypair, metrics).await
+    }
+
+    /// Wrap a client that was built and connected elsewhere. `topics` must be the topics it is
+    /// subscribed to, and `marshal_endpoint` and `keypair` what it connected with, as we use
+    /// them to reconnect if the connection drops.
+    ///
+    /// This is an escape hatch for experimenting with CDN features we don't expose yet; prefer
+    /// [`PushCdnNetwork::new`].
+    pub fn from_client(
+        client: PushCdnClient<TYPES>,
+        marshal_endpoint: String,
+        keypair: KeyPair<WrappedSignatureKey<TYPES::SignatureKey>>,
+        topics: Vec<Topic>,
+    ) -> Self {
+        let KeyPair {
+            public_key: WrappedSignatureKey(public_key),
+            private_key,
+        } = keypair;
+        let connection = ConnectionDetails {
+            marshal_endpoint,
+            public_key,
+            private_key,
+        };
+
+        Self::wrap(client, connection, topics, PushCdnMetrics::default())
+    }
+
+    /// Connect to the marshal, subscribing to `topics`, and spawn the task that receives
+    /// messages from the resulting client. Records metrics to `metrics`.
+    ///
+    /// # Errors
+    /// If we fail to connect
+    async fn connect(
+        connection: ConnectionDetails<TYPES>,
+        topics: Vec<Topic>,
+        metrics: PushCdnMetrics,
+    ) -> anyhow::Result<Self> {
+        let client = connection.connect(topics.clone()).await?;
+        Ok(Self::wrap(client, connection, topics, metrics))
+    }
+
+    /// Wrap a connected client, subscribed to `topics`, and spawn the task that receives
+    /// messages from it. Records metrics to `metrics`.
+    fn wrap(
+        client: PushCdnClient<TYPES>,
+        connection: ConnectionDetails<TYPES>,
+        topics: Vec<Topic>,
+        metrics: PushCdnMetrics,
+    ) -> Self {
+        let client = Arc::new(RwLock::new(Some(client)));
+        for topic in &topics {
+            metrics.topic(topic).subscribed.set(1);
+        }
+
+        let (sender, receiver) = unbounded();
+        let (resume_receiving, resume) = unbounded();
+        let outstanding_pings = Arc::new(OutstandingPings::default());
+        let connection_state = Arc::new(ConnectionState::new(&metrics));
+        let receive_task = Self::spawn_receive_task(
+            client.clone(),
+            sender,
+            resume,
+            outstanding_pings.clone(),
+            connection_state.clone(),
+        );
+
+        Self {
+            client,
+            receiver: Arc::new(receiver),
+            receive_task: Arc::new(RwLock::new(Some(receive_task))),
+            keepalive_task: Arc::default(),
+            send_queue: None,
+            resume_receiving,
+            connection: Arc::new(connection),
+            connection_state,
+            outstanding_pings,
+            retry_policy: RetryPolicy::default(),
+            reconnect_policy: RetryPolicy::default_reconnect(),
+            max_batch: 1,
+            receive_timeout: None,
+            direct_fanout_threshold: DEFAULT_DIRECT_FANOUT_THRESHOLD,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            compress: false,
+            metrics: Arc::new(metrics),
+            subscriptions: Arc::new(RwLock::new(topics.into_iter().collect())),
+            queued_lookups: Arc::default(),
+            // Start unpaused
+            is_paused: Arc::from(AtomicBool::new(false)),
+            in_flight_message_count: Arc::from(AtomicUsize::new(0)),
+            bytes_sent: Arc::default(),
+            future_version_warnings: Arc::default(),
+            inbound_rate_limiter: None,
+            #[cfg(feature = "hotshot-testing")]
+            reliability_config: None,
+            #[cfg(feature = "hotshot-testing")]
+            send_delay: Duration::ZERO,
+            #[cfg(feature = "hotshot-testing")]
+            receive_ordering: None,
+        }
+    }
+
+    /// Spawn the task that receives messages from the client and forwards their payloads (or
+    /// the error we got receiving them) to `recv_msgs`, and answers the pings we are waiting
+    /// on. Frames that are neither, including pings we aren't waiting on, are dropped here, so
+    /// `recv_msgs` only ever wakes up for something useful. After forwarding an error, marks us
+    /// disconnected and waits on `resume` until `recv_msgs` has tried to reconnect. Exits when
+    /// the receiving end is dropped or the client has been shut down.
+    fn spawn_receive_task(
+        client: Arc<RwLock<Option<PushCdnClient<TYPES>>>>,
+        sender: UnboundedSender<ReceivedMessage>,
+        resume: UnboundedReceiver<()>,
+        outstanding_pings: Arc<OutstandingPings>,
+        connection_state: Arc<ConnectionState>,
+    ) -> JoinHandle<()> {
+        async_spawn(async move {
+            loop {
+                let Some(client) = client.read().await.clone() else {
+                    break;
+                };
+
+                let message = client.receive_message().await.map_err(|error| {
+                    error!("failed to receive message: {error}");
+                    let source = match error {
+                        CdnError::Authentication(reason) => {
+                            PushCdnNetworkError::AuthenticationFailed { reason }
+                        }
+                        _ => PushCdnNetworkError::FailedToReceive,
+                    };
+                    NetworkError::PushCdnNetwork { source }
+                });
+
+                // Answer our own pings here, and skip anything that isn't a consensus message,
+                // so neither reaches `recv_msgs`
+                let message = match message.map(ReceivedFrame::classify) {
+                    Ok(ReceivedFrame::Payload(payload, origin)) => Ok((payload, origin)),
+                    Ok(ReceivedFrame::Ping(nonce)) => {
+                        if !outstanding_pings.answer(nonce) {
+                            debug!("dropping a ping we aren't waiting on");
+                        }
+                        continue;
+                    }
+                    Ok(ReceivedFrame::Control) => {
+                        debug!("skipping a frame that isn't a consensus message");
+                        continue;
+                    }
+                    Err(err) => Err(err),
+                };
+
+                let failed = message.is_err();
+                if failed {
+                    connection_state.set_connected(false);
+                }
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+
+                // Don't hammer the broken client while we reconnect
+                if failed && resume.recv().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Set the number of recipients below which broadcasts are sent directly to each recipient
+    /// instead of to the whole topic. A threshold of 0, the default, always broadcasts to the
+    /// topic.
+    #[must_use]
+    pub fn with_direct_fanout_threshold(mut self, direct_fanout_threshold: usize) -> Self {
+        self.direct_fanout_threshold = direct_fanout_threshold;
+        self
+    }
+
+    /// Set the largest serialized message we will send, in bytes. Larger messages fail with
+    /// `NetworkError::MessageTooLarge` instead of being handed to the CDN.
+    #[must_use]
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Set whether we compress the messages we send. Compressed and uncompressed messages can
+    /// be received either way, so nodes can turn this on one at a time.
+    #[must_use]
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Set the policy used to retry failed sends
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the policy used to reconnect to the marshal after we fail to receive. A policy with
+    /// no attempts never reconnects.
+    #[must_use]
+    pub fn with_reconnect_policy(mut self, reconnect_policy: RetryPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Set the most messages `recv_msgs` returns at once. Beyond the first, it only takes
+    /// messages that are already available, so it never waits to fill a batch. Values below 1
+    /// are treated as 1.
+    #[must_use]
+    pub fn with_max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Set how long `recv_msgs` waits for a message before returning an empty batch, so that
+    /// callers can check on other things (like whether they should shut down) when the network
+    /// is quiet. By default, `recv_msgs` waits indefinitely.
+    #[must_use]
+    pub fn with_receive_timeout(mut self, receive_timeout: Duration) -> Self {
+        self.receive_timeout = Some(receive_timeout);
+        self
+    }
+
+    /// Set how we keep our connection alive. With a keepalive interval, spawns a task that pings
+    /// us through the CDN that often, marking us disconnected (see `connection_events`) if a
+    /// ping isn't answered within the max idle timeout. Replaces any previous keepalive task;
+    /// the task stops when we are shut down.
+    #[must_use]
+    pub fn with_connection_tuning(self, tuning: &ConnectionTuning) -> Self {
+        let keepalive_task = tuning.keepalive_interval.map(|interval| {
+            let network = self.clone();
+            let max_idle_timeout = tuning.max_idle_timeout;
+            async_spawn(async move {
+                loop {
+                    async_sleep(interval).await;
+                    match network.ping_within(max_idle_timeout).await {
+                        Ok(_) => {}
+                        Err(NetworkError::ShutDown) => break,
+                        Err(err) => warn!("keepalive ping failed: {err}"),
+                    }
+                }
+            })
+        });
+
+        let previous = std::mem::replace(
+            &mut *self
+                .keepalive_task
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            keepalive_task,
+        );
+        if let Some(previous) = previous {
+            async_spawn(cancel_task(previous));
+        }
+        self
+    }
+
+    /// Queue our sends, rather than sending each inline, so that an urgent message isn't held up
+    /// behind a slow broadcast. A task sends them one at a time, urgent sends (by `config`'s
+    /// priorities) first; each send still waits until its message is actually sent, or the
+    /// queue rejects it for being full. Replaces any previous queue, dropping the sends waiting
+    /// in it. Without a send queue, which is the default, every send goes straight to the
+    /// client.
+    #[must_use]
+    pub fn with_send_queue(mut self, config: SendQueueConfig) -> Self {
+        if let Some(previous) = self.send_queue.replace(Arc::new(SendQueue::spawn(config))) {
+            async_spawn(async move { previous.shut_down().await });
+        }
+        self
+    }
+
+    /// Limit how fast we accept inbound messages, dropping those over the allowance for how
+    /// they reached us before they are decoded, so that a flood of direct messages doesn't
+    /// crowd out broadcasts or the other way round. See [`InboundRateLimit`]. Without a limit,
+    /// which is the default, every message is accepted.
+    #[must_use]
+    pub fn with_inbound_rate_limit(mut self, limit: InboundRateLimit) -> Self {
+        self.inbound_rate_limiter = Some(Arc::new(StdMutex::new(InboundRateLimiter::new(
+            limit,
+            Instant::now(),
+        ))));
+        self
+    }
+
+    /// Receive messages in a deterministic order, so that a test with a fixed seed sees the same
+    /// interleaving every run. See [`ReceiveOrdering`].
+    #[cfg(feature = "hotshot-testing")]
+    #[must_use]
+    pub fn with_receive_ordering(mut self, ordering: ReceiveOrdering) -> Self {
+        self.receive_ordering = Some(Arc::new(Mutex::new(OrderedReceiver {
+            rng: StdRng::seed_from_u64(ordering.seed),
+            config: ordering,
+            ready: VecDeque::new(),
+        })));
+        self
+    }
+
+    /// Run `send`, for a message with `purpose` sent directly or broadcast, through our send
+    /// queue if we have one, or right away if not.
+    ///
+    /// # Errors
+    /// - If `send` fails
+    /// - `PushCdnNetworkError::SendQueueFull` if our send queue is full
+    /// - If we were shut down while `send` was queued
+    async fn dispatch<Fut>(
+        &self,
+        purpose: MessagePurpose,
+        direct: bool,
+        send: Fut,
+    ) -> Result<(), NetworkError>
+    where
+        Fut: Future<Output = Result<(), NetworkError>> + Send + 'static,
+    {
+        match &self.send_queue {
+            Some(queue) => {
+                let priority = queue.config.priority(purpose, direct);
+                queue.send(priority, Box::pin(send)).await
+            }
+            None => send.await,
+        }
+    }
+
+    /// Turn this network into a stream of the messages it receives, one at a time, for use
+    /// with `StreamExt` combinators instead of calling `recv_msgs` in a loop. Messages received
+    /// while paused are dropped, as with `recv_msgs`. Errors are yielded and receiving carries
+    /// on, except that the stream ends once we are shut down or after yielding
+    /// `NetworkError::ReceiveChannelClosed`, as receiving can't recover from either.
+    ///
+    /// The network is cloned cheaply, so keep a clone to send with or shut it down.
+    #[must_use]
+    pub fn into_message_stream(self) -> impl Stream<Item = Result<Message<TYPES>, NetworkError>> {
+        stream::unfold(Some((self, VecDeque::new())), |state| async move {
+            let (network, mut buffered) = state?;
+            loop {
+                if let Some(message) = buffered.pop_front() {
+                    return Some((Ok(message), Some((network, buffered))));
+                }
+
+                match network.recv_msgs().await {
+                    Ok(messages) => buffered.extend(messages),
+                    Err(NetworkError::ShutDown) => return None,
+                    Err(err @ NetworkError::ReceiveChannelClosed) => return Some((Err(err), None)),
+                    Err(err) => return Some((Err(err), Some((network, buffered)))),
+                }
+            }
+        })
+    }
+
+    /// Receive a batch of messages, and how each reached us, in the order they arrive, as
+    /// described on `recv_msgs`
+    ///
+    /// # Errors
+    /// As `recv_msgs`
+    async fn receive(&self) -> Result<Vec<(Message<TYPES>, MessageOrigin)>, NetworkError> {
+        let first = loop {
+            if self.is_shut_down().await {
+                return Err(NetworkError::ShutDown);
+            }
+
+            // Receive a message. If the channel is closed, retrying won't help
+            let received = match self.receive_timeout {
+                Some(timeout) => match async_timeout(timeout, self.receiver.recv()).await {
+                    Ok(received) => received,
+                    // Nothing arrived in time; let the caller decide whether to call again
+                    Err(_) => return Ok(vec![]),
+                },
+                None => self.receiver.recv().await,
+            };
+            let Ok(message) = received else {
+                if self.is_shut_down().await {
+                    return Err(NetworkError::ShutDown);
+                }
+
+                error!("receive channel closed, the network needs to be rebuilt");
+                return Err(NetworkError::ReceiveChannelClosed);
+            };
+
+            match message {
+                Ok(message) => break message,
+                // If we couldn't reconnect, return the error so the caller retries
+                Err(err) => self.recover_from_receive_error(err).await?,
+            }
+        };
+
+        // Take whatever else is ready, without waiting
+        let mut messages = vec![first];
+        while messages.len() < self.max_batch {
+            match self.receiver.try_recv() {
+                Ok(Ok(message)) => messages.push(message),
+                Ok(Err(err)) => {
+                    // Hand back what we have; the caller will see the error next time if we
+                    // couldn't reconnect
+                    if let Err(err) = self.recover_from_receive_error(err).await {
+                        warn!("failed to recover mid-batch, returning what we have: {err}");
+                    }
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        // If we're paused, receive but don't process messages
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(vec![]);
+        }
+
+        let mut deserialized = Vec::with_capacity(messages.len());
+        let mut last_error = None;
+        for (message, origin) in messages {
+            // Drop what is over our inbound rate limit before spending any work on it
+            if !self.within_rate_limit(&origin) {
+                debug!("dropped a message over our inbound rate limit, from {origin:?}");
+                self.metrics.receive_rate_limited.add(1);
+                continue;
+            }
+
+            let frame = decode_frame(&message, self.max_message_size);
+
+            // A peer that upgraded before us isn't our error, so don't fail over it
+            if let Some(version) = frame.as_deref().ok().and_then(future_version) {
+                self.skip_future_version(version);
+                continue;
+            }
+
+            // Deserialize it with whichever version it was serialized with
+            let message = frame.and_then(|message| deserialize_versioned(&message));
+            match message {
+                Ok(message) => {
+                    self.metrics.receive_successes.add(1);
+                    deserialized.push((message, origin));
+                }
+                Err(err) => {
+                    self.metrics.receive_errors.add(1);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        // Only fail if nothing in the batch was usable
+        match last_error {
+            Some(err) if deserialized.is_empty() => Err(err),
+            Some(err) => {
+                warn!("dropped a message that failed to deserialize: {err}");
+                Ok(deserialized)
+            }
+            None => Ok(deserialized),
+        }
+    }
+
+    /// Receive a batch of messages as `recv_msgs` does, along with how each one reached us:
+    /// broadcast to which topics, or sent directly to us.
+    ///
+    /// # Errors
+    /// As `recv_msgs`
+    pub async fn recv_msgs_with_meta(
+        &self,
+    ) -> Result<Vec<(Message<TYPES>, MessageOrigin)>, NetworkError> {
+        #[cfg(feature = "hotshot-testing")]
+        if let Some(ordering) = &self.receive_ordering {
+            return self.receive_ordered(ordering).await;
+        }
+
+        self.receive().await
+    }
+
+    /// Skip a message serialized with `version`, which is newer than any we support, as peers
+    /// that upgrade before us will send during a rolling upgrade. Counts it, but only warns
+    /// about each version once every `FUTURE_VERSION_WARNING_INTERVAL`, so a stream of them
+    /// doesn't flood the logs.
+    fn skip_future_version(&self, version: Version) {
+        self.metrics.receive_future_version.add(1);
+
+        let now = Instant::now();
+        let mut warnings = self
+            .future_version_warnings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match warnings.entry((version.major, version.minor)) {
+            Entry::Vacant(entry) => {
+                warn!(
+                    "skipping messages with unsupported future version {version}, latest \
+                     supported is {VERSION_0_1}; this node may need upgrading"
+                );
+                entry.insert((now, 0));
+            }
+            Entry::Occupied(mut entry) => {
+                let (last_warned, skipped) = entry.get_mut();
+                *skipped += 1;
+                if now.duration_since(*last_warned) >= FUTURE_VERSION_WARNING_INTERVAL {
+                    warn!(
+                        "skipped {skipped} more messages with unsupported future version \
+                         {version}, latest supported is {VERSION_0_1}; this node may need \
+                         upgrading"
+                    );
+                    *last_warned = now;
+                    *skipped = 0;
+                }
+            }
+        }
+    }
+
+    /// Whether a message that reached us by `origin` is within our inbound rate limit, if we
+    /// have one. If so, it is taken from that origin's allowance.
+    fn within_rate_limit(&self, origin: &MessageOrigin) -> bool {
+        let Some(limiter) = &self.inbound_rate_limiter else {
+            return true;
+        };
+
+        limiter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .admit(origin, Instant::now())
+    }
+
+    /// Receive messages in an order that depends only on which messages arrive and on the
+    /// ordering's seed, not on when they arrive. Messages are gathered into batches of
+    /// `batch_size` (or fewer, if no more arrive for `RECEIVE_ORDERING_SETTLE_TIME`), put in a
+    /// canonical order, and then shuffled with the seeded RNG. Each call hands back up to
+    /// `max_batch` messages from the current batch.
+    ///
+    /// # Errors
+    /// As `recv_msgs`, if nothing was received before the error
+    #[cfg(feature = "hotshot-testing")]
+    async fn receive_ordered(
+        &self,
+        ordering: &Mutex<OrderedReceiver<TYPES>>,
+    ) -> Result<Vec<(Message<TYPES>, MessageOrigin)>, NetworkError> {
+        let mut ordering = ordering.lock().await;
+
+        if ordering.ready.is_empty() {
+            let mut batch = Vec::new();
+            while batch.len() < ordering.config.batch_size {
+                let received = if batch.is_empty() {
+                    self.receive().await?
+                } else {
+                    match async_timeout(RECEIVE_ORDERING_SETTLE_TIME, self.receive()).await {
+                        Ok(Ok(received)) => received,
+                        Ok(Err(err)) => {
+                            warn!("failed to receive, releasing a partial batch: {err}");
+                            break;
+                        }
+                        Err(_) => break,
+                    }
+                };
+                // Nothing arrived in time, or we're paused
+                if received.is_empty() {
+                    break;
+                }
+                batch.extend(received);
+            }
+
+            // Sort by encoding, so the shuffle starts from the same order however the batch
+            // arrived
+            batch.sort_by_cached_key(|(message, _)| {
+                bincode_opts().serialize(message).unwrap_or_default()
+            });
+            batch.shuffle(&mut ordering.rng);
+            ordering.ready.extend(batch);
+        }
+
+        let count = ordering.ready.len().min(self.max_batch);
+        Ok(ordering.ready.drain(..count).collect())
+    }
+
+    /// Handle an error the receive task forwarded (and already logged) by trying to reconnect
+    /// (or to authenticate again, if the marshal rejected us), then letting the receive task
+    /// carry on, with the new client if we got one.
+    ///
+    /// # Errors
+    /// `err`, if we couldn't reconnect
+    async fn recover_from_receive_error(&self, err: NetworkError) -> Result<(), NetworkError> {
+        self.metrics.receive_errors.add(1);
+        let reconnected = if RetryPolicy::needs_reauthentication(&err) {
+            self.reauthenticate().await
+        } else {
+            self.reconnect().await
+        };
+
+        let _ = self.resume_receiving.send(()).await;
+
+        reconnected.map_err(|_| err)
+    }
+
+    /// Replace our client with a fresh connection to the marshal, subscribed to our current
+    /// topics, backing off between attempts according to our reconnect policy. Our topics are
+    /// subscribed to again before the new client is used for anything, including receiving.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If every attempt failed
+    async fn reconnect(&self) -> Result<(), NetworkError> {
+        let max_attempts = self.reconnect_policy.max_attempts;
+
+        for attempt in 1..=max_attempts {
+            async_sleep(self.reconnect_policy.delay(attempt - 1)).await;
+
+            // Hold our subscriptions until the new client is in place, so that a concurrent
+            // (un)subscription can't land on the client we are replacing and be lost
+            let subscriptions = self.subscriptions.read().await;
+            let topics: Vec<Topic> = subscriptions.clone().into_iter().collect();
+            let timeout = self.reconnect_policy.attempt_timeout;
+            match async_timeout(timeout, self.connect_subscribed(topics)).await {
+                Ok(Ok(client)) => {
+                    let mut current = self.client.write().await;
+                    // Don't resurrect a client we shut down while reconnecting
+                    if current.is_none() {
+                        return Err(NetworkError::ShutDown);
+                    }
+                    *current = Some(client);
+                    self.connection_state.set_connected(true);
+
+                    warn!("reconnected to the marshal (attempt {attempt}/{max_attempts})");
+                    return Ok(());
+                }
+                Ok(Err(err)) => {
+                    warn!(
+                        "failed to reconnect to the marshal \
+                         (attempt {attempt}/{max_attempts}): {err}"
+                    );
+                }
+                Err(_) => {
+                    warn!(
+                        "timed out reconnecting to the marshal (attempt {attempt}/{max_attempts})"
+                    );
+                }
+            }
+
+            if self.is_shut_down().await {
+                return Err(NetworkError::ShutDown);
+            }
+        }
+
+        error!("failed to reconnect to the marshal after {max_attempts} attempts");
+        Err(NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::FailedToReceive,
+        })
+    }
+
+    /// Connect to the marshal, then subscribe to `topics` explicitly, rather than trusting the
+    /// handshake to have. Subscribing is idempotent: the broker ignores topics we are already
+    /// subscribed to.
+    ///
+    /// # Errors
+    /// If we fail to connect or to subscribe
+    async fn connect_subscribed(&self, topics: Vec<Topic>) -> anyhow::Result<PushCdnClient<TYPES>> {
+        let client = self.connection.connect(topics.clone()).await?;
+        client.subscribe(topics).await?;
+        Ok(client)
+    }
+
+    /// Run the marshal handshake again after the marshal rejected us, replacing our client with
+    /// the result, backing off between attempts according to our reconnect policy.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If every attempt failed
+    async fn reauthenticate(&self) -> Result<(), NetworkError> {
+        warn!("authenticating with the marshal again");
+        self.reconnect().await?;
+
+        self.metrics.reauthentications.add(1);
+        Ok(())
+    }
+
+    /// Run `send` with our current client until it succeeds, fails with an error that isn't
+    /// worth retrying, or we run out of attempts, backing off between attempts according to our
+    /// retry policy. If the marshal rejected us, authenticates again before the next attempt.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - `PushCdnNetworkError::ConnectionLost` if we lost the connection to the broker
+    /// - `PushCdnNetworkError::SendTimeout` if the last attempt timed out
+    /// - `PushCdnNetworkError::AuthenticationFailed` if we couldn't authenticate again
+    /// - `NetworkError::CouldNotDeliver` if the send failed for any other reason
+    async fn send_with_retry<F, Fut>(&self, send: F) -> Result<(), NetworkError>
+    where
+        F: Fn(PushCdnClient<TYPES>) -> Fut,
+        Fut: Future<Output = Result<(), CdnError>>,
+    {
+        let max_attempts = self.retry_policy.max_attempts;
+
+        let mut attempt = 1;
+        loop {
+            let client = self.client().await?;
+            let err = match async_timeout(self.retry_policy.attempt_timeout, send(client)).await {
+                Ok(Ok(())) => {
+                    self.connection_state.set_connected(true);
+                    return Ok(());
+                }
+                Ok(Err(err)) => classify_send_error(err),
+                Err(_) => NetworkError::PushCdnNetwork {
+                    source: PushCdnNetworkError::SendTimeout,
+                },
+            };
+
+            if attempt < max_attempts && RetryPolicy::needs_reauthentication(&err) {
+                error!("marshal rejected us (attempt {attempt}/{max_attempts}): {err}");
+                self.connection_state.set_connected(false);
+                if self.reauthenticate().await.is_err() {
+                    return Err(err);
+                }
+                attempt += 1;
+            } else if attempt < max_attempts && RetryPolicy::is_retryable(&err) {
+                let delay = self.retry_policy.delay(attempt - 1);
+                warn!(
+                    "failed to send message (attempt {attempt}/{max_attempts}), \
+                     retrying in {delay:?}: {err}"
+                );
+                async_sleep(delay).await;
+                attempt += 1;
+            } else {
+                error!("failed to send message after {attempt} attempts: {err}");
+                if RetryPolicy::is_retryable(&err) || RetryPolicy::needs_reauthentication(&err) {
+                    self.connection_state.set_connected(false);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    /// Get a handle to the underlying client.
+    ///
+    /// # Errors
+    /// If we have been shut down
+    async fn client(&self) -> Result<PushCdnClient<TYPES>, NetworkError> {
+        self.client
+            .read()
+            .await
+            .clone()
+            .ok_or(NetworkError::ShutDown)
+    }
+
+    /// Take the node lookups queued with us, oldest first, so that they can be forwarded to a
+    /// network that looks nodes up. Only the most recent `MAX_QUEUED_LOOKUPS` are kept.
+    pub fn drain_queued_lookups(&self) -> Vec<(ViewNumber, TYPES::SignatureKey)> {
+        self.queued_lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+            .collect()
+    }
+
+    /// Listen for changes in whether we are connected to the CDN. Each change is also logged
+    /// and counted in our metrics. We start out connected, so the first event is always
+    /// [`ConnectionEvent::Disconnected`].
+    pub fn connection_events(&self) -> mpsc::UnboundedReceiver<ConnectionEvent> {
+        self.connection_state.listen()
+    }
+
+    /// Get a handle to the underlying CDN client, for features we don't expose yet (custom
+    /// message types, client statistics, and so on). Here be dragons: anything done with it
+    /// bypasses pausing, retries, metrics, and connection tracking, and anything it receives
+    /// is taken from under `recv_msgs`. After a reconnect, the handle refers to the old client.
+    ///
+    /// # Errors
+    /// If we have been shut down
+    pub async fn raw_client(&self) -> Result<PushCdnClient<TYPES>, NetworkError> {
+        self.client().await
+    }
+
+    /// Whether or not we have been shut down
+    async fn is_shut_down(&self) -> bool {
+        self.client.read().await.is_none()
+    }
+
+    /// Check that we are still connected by sending ourselves a direct message and waiting for
+    /// it to come back through the CDN, within our retry policy's attempt timeout. Updates
+    /// the connection state that `is_ready` reports.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - `PushCdnNetworkError::ConnectionLost` if we lost the connection to the broker
+    /// - `PushCdnNetworkError::SendTimeout` if the ping didn't come back in time
+    /// - `NetworkError::CouldNotDeliver` if the ping failed to send for any other reason
+    pub async fn ping(&self) -> Result<Duration, NetworkError> {
+        self.ping_within(self.retry_policy.attempt_timeout).await
+    }
+
+    /// Ping ourselves through the CDN, as in `ping`, giving up after `timeout`.
+    ///
+    /// # Errors
+    /// As `ping`
+    async fn ping_within(&self, timeout: Duration) -> Result<Duration, NetworkError> {
+        let client = self.client().await?;
+        let recipient = WrappedSignatureKey(self.connection.public_key.clone());
+
+        let mut pending = self.outstanding_pings.start();
+        let mut ping = PING_PREFIX.to_vec();
+        ping.extend_from_slice(&pending.nonce.to_le_bytes());
+
+        let start = Instant::now();
+        let result = async_timeout(timeout, async {
+            client
+                .send_direct_message(&recipient, ping)
+                .await
+                .map_err(classify_send_error)?;
+
+            // The receive task only drops our answer if it exits, when we've been shut down
+            (&mut pending.answered)
+                .await
+                .map_err(|_| NetworkError::ShutDown)?;
+            Ok(start.elapsed())
+        })
+        .await
+        .unwrap_or(Err(NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::SendTimeout,
+        }));
+
+        self.connection_state.set_connected(result.is_ok());
+        result
+    }
+
+    /// Get the topics we are currently subscribed to
+    pub async fn subscriptions(&self) -> BTreeSet<Topic> {
+        self.subscriptions.read().await.clone()
+    }
+
+    /// Get the topics we are currently subscribed to, as a list in the same (stable) order as
+    /// [`Self::subscriptions`]
+    pub async fn subscribed_topics(&self) -> Vec<Topic> {
+        self.subscriptions().await.into_iter().collect()
+    }
+
+    /// Subscribe to `topics`, starting to receive messages broadcast to them. Topics we are
+    /// already subscribed to are ignored.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If the client fails to subscribe
+    pub async fn subscribe(&self, topics: Vec<Topic>) -> Result<(), NetworkError> {
+        // Hold the lock for the duration so concurrent (un)subscriptions don't interleave, and
+        // take the client under it, so a reconnect can't replace it before we use it
+        let mut subscriptions = self.subscriptions.write().await;
+        let client = self.client().await?;
+
+        // Only subscribe to the topics we aren't already subscribed to
+        let topics: Vec<Topic> = topics
+            .into_iter()
+            .filter(|topic| !subscriptions.contains(topic))
+            .collect();
+        if topics.is_empty() {
+            return Ok(());
+        }
+
+        client.subscribe(topics.clone()).await.map_err(|err| {
+            error!("failed to subscribe to topics: {err}");
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::FailedToSubscribe,
+            }
+        })?;
+
+        for topic in &topics {
+            self.metrics.topic(topic).subscribed.set(1);
+        }
+        subscriptions.extend(topics);
+        Ok(())
+    }
+
+    /// Unsubscribe from `topics`, no longer receiving messages broadcast to them. Topics we are
+    /// not subscribed to are ignored.
+    ///
+    /// # Errors
+    /// - If `topics` contains `Topic::Global`, which every node must stay subscribed to
+    /// - If we have been shut down
+    /// - If the client fails to unsubscribe
+    pub async fn unsubscribe(&self, topics: Vec<Topic>) -> Result<(), NetworkError> {
+        if topics.contains(&Topic::Global) {
+            return Err(NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::CannotUnsubscribeFromGlobal,
+            });
+        }
+
+        // Hold the lock for the duration so concurrent (un)subscriptions don't interleave, and
+        // take the client under it, so a reconnect can't replace it before we use it
+        let mut subscriptions = self.subscriptions.write().await;
+        let client = self.client().await?;
+
+        // Only unsubscribe from the topics we are actually subscribed to
+        let topics: Vec<Topic> = topics
+            .into_iter()
+            .filter(|topic| subscriptions.contains(topic))
+            .collect();
+        if topics.is_empty() {
+            return Ok(());
+        }
+
+        client.unsubscribe(topics.clone()).await.map_err(|err| {
+            error!("failed to unsubscribe from topics: {err}");
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::FailedToUnsubscribe,
+            }
+        })?;
+
+        for topic in &topics {
+            self.metrics.topic(topic).subscribed.set(0);
+            subscriptions.remove(topic);
+        }
+        Ok(())
+    }
+
+    /// Cancel the receive task, closing the channel that `recv_msgs` reads from
+    async fn cancel_receive_task(&self) {
+        if let Some(task) = self.receive_task.write().await.take() {
+            cancel_task(task).await;
+        }
+    }
+
+    /// Kill the receive task, closing the channel that `recv_msgs` reads from. This simulates
+    /// the background task dying.
+    #[cfg(feature = "hotshot-testing")]
+    pub async fn kill_receive_task(&self) {
+        self.cancel_receive_task().await;
+    }
+
+    /// Drop our connection to the broker and reconnect, as we would after losing it
+    ///
+    /// # Errors
+    /// If we couldn't reconnect
+    #[cfg(feature = "hotshot-testing")]
+    pub async fn drop_connection(&self) -> Result<(), NetworkError> {
+        self.connection_state.set_connected(false);
+        self.reconnect().await
+    }
+
+    /// Stall all sends until the returned guard is dropped. This simulates a stalled broker.
+    #[cfg(feature = "hotshot-testing")]
+    pub async fn stall_sends(&self) -> RwLockWriteGuard<'_, Option<PushCdnClient<TYPES>>> {
+        self.client.write().await
+    }
+
+    /// Run `send`, counting it as in-flight until it resolves (successfully or not).
+    async fn track_in_flight<Fut>(&self, send: Fut) -> Result<(), NetworkError>
+    where
+        Fut: Future<Output = Result<(), NetworkError>>,
+    {
+        self.in_flight_message_count.fetch_add(1, Ordering::Relaxed);
+        let result = send.await;
+        self.in_flight_message_count.fetch_sub(1, Ordering::Relaxed);
+
+        result
+    }
+
+    /// Wait, for at most `timeout`, until every send that is in flight (including those
+    /// waiting in the send queue) has resolved, successfully or not. Sends made while we wait
+    /// are waited for too.
+    ///
+    /// # Errors
+    /// `NetworkError::Timeout` if sends are still in flight after `timeout`
+    pub async fn flush(&self, timeout: Duration) -> Result<(), NetworkError> {
+        async_timeout(timeout, async {
+            while self.in_flight_message_count.load(Ordering::Relaxed) > 0 {
+                async_sleep(FLUSH_POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|source| NetworkError::Timeout { source })
+    }
+
+    /// How many bytes we have broadcast to `topic`, over the lifetime of this network. Counts
+    /// each successful broadcast once, as framed for the wire (after any compression), however
+    /// many retries it took. A broadcast to several topics counts toward each of them.
+    ///
+    /// Sends handed to a reliability config in tests aren't counted, since it decides how many
+    /// times they go out.
+    #[must_use]
+    pub fn bytes_sent(&self, topic: Topic) -> u64 {
+        self.bytes_sent.topic(&topic).load(Ordering::Relaxed)
+    }
+
+    /// How many bytes we have sent in direct messages, counted as in
+    /// [`PushCdnNetwork::bytes_sent`]
+    #[must_use]
+    pub fn direct_bytes_sent(&self) -> u64 {
+        self.bytes_sent.direct.load(Ordering::Relaxed)
+    }
+
+    /// How many bytes we have sent altogether, broadcast and direct, counted as in
+    /// [`PushCdnNetwork::bytes_sent`]. A broadcast to several topics counts once.
+    #[must_use]
+    pub fn total_bytes_sent(&self) -> u64 {
+        self.bytes_sent.total.load(Ordering::Relaxed)
+    }
+
+    /// Spawn a send that has been passed through our reliability config, counting it as
+    /// in-flight until it resolves.
+    #[cfg(feature = "hotshot-testing")]
+    fn spawn_chaos_send(&self, send: BoxSyncFuture<'static, ()>) {
+        let in_flight_message_count = Arc::clone(&self.in_flight_message_count);
+        in_flight_message_count.fetch_add(1, Ordering::Relaxed);
+
+        async_spawn(async move {
+            send.await;
+            in_flight_message_count.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Broadcast a message to members of the particular topic. Retries according to our
+    /// retry policy.
+    ///
+    /// If `recipients` is non-empty and smaller than our direct fan-out threshold, the message
+    /// is instead sent directly to each recipient. An empty set means everyone on the topic.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If we fail to serialize the message
+    /// - If we fail to send the broadcast message after exhausting our retries.
+    /// - If we fail to send the message to any of the recipients, when fanning out
+    async fn broadcast_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        topic: Topic,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        if !recipients.is_empty() && recipients.len() < self.direct_fanout_threshold {
+            return self.fan_out(message, recipients, bind_version).await;
+        }
+
+        self.broadcast_to_topic(message, topic, bind_version).await
+    }
+
+    /// Broadcast a message to every member of `topic`, regardless of who they are. The quorum
+    /// and DA broadcasts are this with `Topic::Global` and `Topic::DA`. Retries according to
+    /// our retry policy.
+    ///
+    /// Anyone can broadcast to a topic, but only nodes subscribed to it (when created, or
+    /// later with [`PushCdnNetwork::subscribe`]) receive the message. Every node is
+    /// subscribed to `Topic::Global`.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If we fail to serialize the message
+    /// - If we fail to send the broadcast message after exhausting our retries.
+    pub async fn broadcast_to_topic<Ver: StaticVersionType>(
+        &self,
+        message: Message<TYPES>,
+        topic: Topic,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        self.broadcast_message_multi(message, vec![topic], bind_version)
+            .await
+    }
+
+    /// Broadcast a message to the members of all of `topics` at once. The message is serialized
+    /// once, so every topic sees the same bytes. Retries according to our retry policy.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If we fail to serialize the message
+    /// - If we fail to send the broadcast message after exhausting our retries.
+    pub async fn broadcast_message_multi<Ver: StaticVersionType>(
+        &self,
+        message: Message<TYPES>,
+        topics: Vec<Topic>,
+        _: Ver,
+    ) -> Result<(), NetworkError> {
+        // If we're paused, don't send the message
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // If we're standing in for a secondary network, hold the message back
+        #[cfg(feature = "hotshot-testing")]
+        self.delay_send().await;
+
+        // Bincode the message
+        let serialized_message = self.serialize::<Ver>(&message)?;
+
+        // If we have a reliability config, let it decide whether, when, and how many times to
+        // send the message
+        #[cfg(feature = "hotshot-testing")]
+        if let Some(config) = &self.reliability_config {
+            let client = self.client().await?;
+            let send = config.chaos_send_msg(
+                serialized_message,
+                Arc::new(move |message: Vec<u8>| {
+                    let client = client.clone();
+                    let topics = topics.clone();
+                    boxed_sync(async move {
+                        if let Err(err) = client.send_broadcast_message(topics, message).await {
+                            warn!("failed to send message: {err}");
+                        }
+                    })
+                }),
+            );
+            self.spawn_chaos_send(send);
+            return Ok(());
+        }
+
+        // Send the message, through our send queue if we have one
+        let size = serialized_message.len();
+        let network = self.clone();
+        let send_topics = topics.clone();
+        let send = async move {
+            network
+                .send_with_retry(|client| {
+                    let topics = send_topics.clone();
+                    let serialized_message = serialized_message.clone();
+                    async move {
+                        client
+                            .send_broadcast_message(topics, serialized_message)
+                            .await
+                    }
+                })
+                .await
+        };
+        let result = self
+            .track_in_flight(self.dispatch(message.purpose(), false, send))
+            .await;
+
+        if result.is_ok() {
+            self.bytes_sent.broadcast(&topics, size);
+            self.metrics.broadcasts_sent.add(1);
+            for topic in &topics {
+                self.metrics.topic(topic).broadcasts_sent.add(1);
+            }
+        }
+        result
+    }
+
+    /// Send a message directly to each of `recipients`. Keeps going if sending to one of them
+    /// fails.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message
+    /// - If we failed to send the message to any of the recipients
+    async fn fan_out<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        let failures = self
+            .direct_message_multi(message, recipients.into_iter().collect(), bind_version)
+            .await?;
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        Err(NetworkError::MultipleErrors {
+            errors: failures.into_values().map(Box::new).collect(),
+        })
+    }
+
+    /// Send the same message directly to each of `recipients` at once, serializing it only
+    /// once. Retries each send according to our retry policy, and keeps going if sending to
+    /// some of the recipients fails.
+    ///
+    /// Returns the recipients we failed to send to, with the reason; every other recipient
+    /// was sent the message.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message
+    /// - If the serialized message is larger than our maximum message size
+    pub async fn direct_message_multi<Ver: StaticVersionType>(
+        &self,
+        message: Message<TYPES>,
+        recipients: Vec<TYPES::SignatureKey>,
+        _: Ver,
+    ) -> Result<BTreeMap<TYPES::SignatureKey, NetworkError>, NetworkError> {
+        // If we're paused, don't send the message
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(BTreeMap::new());
+        }
+
+        // If we're standing in for a secondary network, hold the message back
+        #[cfg(feature = "hotshot-testing")]
+        self.delay_send().await;
+
+        // Bincode the message, once for everyone
+        let serialized_message = self.serialize::<Ver>(&message)?;
+        let purpose = message.purpose();
+
+        let sends = recipients.into_iter().map(|recipient| {
+            let serialized_message = serialized_message.clone();
+            async move {
+                let result = self
+                    .send_direct(
+                        serialized_message,
+                        WrappedSignatureKey(recipient.clone()),
+                        purpose,
+                    )
+                    .await;
+                (recipient, result)
+            }
+        });
+
+        Ok(join_all(sends)
+            .await
+            .into_iter()
+            .filter_map(|(recipient, result)| result.err().map(|err| (recipient, err)))
+            .collect())
+    }
+
+    /// Send an already serialized message with `purpose` directly to `recipient`, retrying
+    /// according to our retry policy.
+    ///
+    /// # Errors
+    /// - If we have been shut down
+    /// - If we fail to send the direct message after exhausting our retries
+    /// - If our send queue is full
+    async fn send_direct(
+        &self,
+        serialized_message: Vec<u8>,
+        recipient: WrappedSignatureKey<TYPES::SignatureKey>,
+        purpose: MessagePurpose,
+    ) -> Result<(), NetworkError> {
+        // If we have a reliability config, let it decide whether, when, and how many times to
+        // send the message
+        #[cfg(feature = "hotshot-testing")]
+        if let Some(config) = &self.reliability_config {
+            let client = self.client().await?;
+            let send = config.chaos_send_msg(
+                serialized_message,
+                Arc::new(move |message: Vec<u8>| {
+                    let client = client.clone();
+                    let recipient = recipient.clone();
+                    boxed_sync(async move {
+                        if let Err(err) = client.send_direct_message(&recipient, message).await {
+                            warn!("failed to send message: {err}");
+                        }
+                    })
+                }),
+            );
+            self.spawn_chaos_send(send);
+            return Ok(());
+        }
+
+        // Send the message, through our send queue if we have one
+        let size = serialized_message.len();
+        let network = self.clone();
+        let send = async move {
+            network
+                .send_with_retry(|client| {
+                    let recipient = recipient.clone();
+                    let serialized_message = serialized_message.clone();
+                    async move {
+                        client
+                            .send_direct_message(&recipient, serialized_message)
+                            .await
+                    }
+                })
+                .await
+        };
+        let result = self
+            .track_in_flight(self.dispatch(purpose, true, send))
+            .await;
+
+        if result.is_ok() {
+            self.bytes_sent.direct(size);
+            self.metrics.direct_messages_sent.add(1);
+        }
+        result
+    }
+
+    /// Wait out our send delay, if we have one.
+    #[cfg(feature = "hotshot-testing")]
+    async fn delay_send(&self) {
+        if !self.send_delay.is_zero() {
+            async_sleep(self.send_delay).await;
+        }
+    }
+
+    /// Serialize a message, recording how long it took, and frame it for the wire,
+    /// compressing it if we are configured to.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message. The error says what kind of message it was, and
+    ///   for which view.
+    /// - If the serialized message is larger than our maximum message size
+    fn serialize<Ver: StaticVersionType>(
+        &self,
+        message: &Message<TYPES>,
+    ) -> Result<Vec<u8>, NetworkError> {
+        let start = Instant::now();
+        let serialized_message = Serializer::<Ver>::serialize(message).map_err(|e| {
+            serialization_error(message.purpose(), message.get_view_number().get_u64(), e)
+        })?;
+        self.metrics
+            .serialize_duration
+            .add_point(start.elapsed().as_secs_f64());
+
+        // Catch oversized messages here, rather than have the broker drop them
+        if serialized_message.len() > self.max_message_size {
+            warn!(
+                "refusing to send {:?} message for view {} of {} bytes, the limit is {}",
+                message.purpose(),
+                message.get_view_number().get_u64(),
+                serialized_message.len(),
+                self.max_message_size
+            );
+            return Err(NetworkError::MessageTooLarge {
+                size: serialized_message.len(),
+                limit: self.max_message_size,
+            });
+        }
+
+        Ok(encode_frame(serialized_message, self.compress))
+    }
+}
+
+/// How many times the test generator picks new ports for a broker or the marshal that failed
+/// to bind before giving up
+#[cfg(feature = "hotshot-testing")]
+const MAX_TEST_BIND_ATTEMPTS: usize = 5;
+
+/// The index (from the all-zero seed) that test broker keys are derived from
+#[cfg(feature = "hotshot-testing")]
+const TEST_BROKER_KEY_INDEX: u64 = 1337;
+
+/// How long a deterministically ordered receive waits for more of a batch before releasing
+/// what it has
+#[cfg(feature = "hotshot-testing")]
+const RECEIVE_ORDERING_SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Receive messages in an order that depends only on which messages arrive and on `seed`, not
+/// on when they arrive, to make consensus bugs reproducible. Received messages are gathered
+/// into batches of `batch_size`, or fewer if no more arrive for a short while; each batch is
+/// put in a canonical order and then shuffled by an RNG seeded with `seed`.
+#[cfg(feature = "hotshot-testing")]
+#[derive(Clone, Debug)]
+pub struct ReceiveOrdering {
+    /// Seeds the RNG that shuffles each batch
+    pub seed: u64,
+    /// How many messages to gather before ordering them
+    pub batch_size: usize,
+}
+
+/// The state of a deterministically ordered receive
+#[cfg(feature = "hotshot-testing")]
+struct OrderedReceiver<TYPES: NodeType> {
+    /// How we order messages
+    config: ReceiveOrdering,
+    /// Shuffles each batch
+    rng: StdRng,
+    /// The ordered messages of the current batch that haven't been received yet, and how
+    /// each reached us
+    ready: VecDeque<(Message<TYPES>, MessageOrigin)>,
+}
+
+/// The shape of the local Push CDN spun up by the test generator
+#[cfg(feature = "hotshot-testing")]
+#[derive(Clone, Debug)]
+pub struct PushCdnTestConfig {
+    /// The number of brokers to run. There is always exactly one marshal.
+    pub num_brokers: usize,
+    /// Whether each broker gets its own keypair. If not, every broker shares one.
+    pub distinct_broker_keys: bool,
+    /// How long every client waits before each outgoing send. Used when the Push CDN is the
+    /// secondary network of a combined network, so that the primary is preferred.
+    pub send_delay: Duration,
+    /// Which topics each client subscribes to. If unset, the first `da_committee_size` nodes
+    /// are on the DA committee.
+    pub topics: Option<TopicAssignment>,
+    /// If set, every client receives messages in a deterministic order
+    pub receive_ordering: Option<ReceiveOrdering>,
+}
+
+#[cfg(feature = "hotshot-testing")]
+impl Default for PushCdnTestConfig {
+    fn default() -> Self {
+        Self {
+            num_brokers: 2,
+            distinct_broker_keys: false,
+            send_delay: Duration::ZERO,
+            topics: None,
+            receive_ordering: None,
+        }
+    }
+}
+
+/// The topics each client spawned by the test generator subscribes to, by node index. Lets
+/// tests build committees that aren't just the first few nodes.
+#[cfg(feature = "hotshot-testing")]
+#[derive(Clone)]
+pub struct TopicAssignment(Arc<dyn Fn(u64) -> Vec<Topic> + Send + Sync>);
+
+#[cfg(feature = "hotshot-testing")]
+impl TopicAssignment {
+    /// Subscribe node `node_id` to `topics(node_id)`
+    pub fn new(topics: impl Fn(u64) -> Vec<Topic> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(topics))
+    }
+
+    /// Every node subscribes to `Topic::Global`, and the first `da_committee_size` nodes to
+    /// `Topic::DA` too
+    #[must_use]
+    pub fn first_nodes_in_da(da_committee_size: usize) -> Self {
+        Self::new(move |node_id| {
+            if node_id < da_committee_size as u64 {
+                vec![Topic::DA, Topic::Global]
+            } else {
+                vec![Topic::Global]
+            }
+        })
+    }
+
+    /// The topics node `node_id` subscribes to
+    #[must_use]
+    pub fn topics(&self, node_id: u64) -> Vec<Topic> {
+        (self.0)(node_id)
+    }
+}
+
+#[cfg(feature = "hotshot-testing")]
+impl std::fmt::Debug for TopicAssignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TopicAssignment").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "hotshot-testing")]
+impl PushCdnTestConfig {
+    /// The keypair the test generator gives broker number `broker`
+    #[must_use]
+    pub fn broker_keypair<K: SignatureKey>(&self, broker: usize) -> (K, K::PrivateKey) {
+        let index = if self.distinct_broker_keys {
+            TEST_BROKER_KEY_INDEX + broker as u64
+        } else {
+            TEST_BROKER_KEY_INDEX
+        };
+
+        K::generated_from_seed_indexed([0u8; 32], index)
+    }
+}
+
+#[cfg(feature = "hotshot-testing")]
+impl<TYPES: NodeType> PushCdnNetwork<TYPES> {
+    /// Pick an unused local port.
+    ///
+    /// # Errors
+    /// If there are no unused ports
+    fn pick_test_port() -> anyhow::Result<u16> {
+        portpicker::pick_unused_port().context("could not find an open port")
+    }
+
+    /// Start a local broker using `discovery_endpoint`, authenticating as the given key. Picks
+    /// new ports and tries again if it fails to bind, as another test may have raced us to them.
+    ///
+    /// # Errors
+    /// If we fail to start the broker after `MAX_TEST_BIND_ATTEMPTS` attempts
+    async fn start_test_broker(
+        discovery_endpoint: &DiscoveryEndpoint,
+        public_key: &TYPES::SignatureKey,
+        private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    ) -> anyhow::Result<()> {
+        for attempt in 1..=MAX_TEST_BIND_ATTEMPTS {
+            // Extrapolate addresses from the ports to bind to
+            let private_address = format!("127.0.0.1:{}", Self::pick_test_port()?);
+            let public_address = format!("127.0.0.1:{}", Self::pick_test_port()?);
+
+            let config: Config<WrappedSignatureKey<TYPES::SignatureKey>> =
+                BrokerConfigBuilder::default()
+                    .discovery_endpoint(discovery_endpoint.to_string())
+                    .keypair(KeyPair {
+                        public_key: WrappedSignatureKey(public_key.clone()),
+                        private_key: private_key.clone(),
+                    })
+                    .metrics_enabled(false)
+                    .private_bind_address(private_address.clone())
+                    .public_bind_address(public_address.clone())
+                    .private_advertise_address(private_address)
+                    .public_advertise_address(public_address)
+                    .build()?;
+
+            // Creating the broker binds its ports
+            let broker: Broker<TestingDef<TYPES>> = match Broker::new(config).await {
+                Ok(broker) => broker,
+                Err(err) => {
+                    warn!(
+                        "failed to start broker \
+                         (attempt {attempt}/{MAX_TEST_BIND_ATTEMPTS}): {err}"
+                    );
+                    continue;
+                }
+            };
+
+            async_spawn(async move {
+                // Error if we stopped unexpectedly
+                if let Err(err) = broker.start().await {
+                    error!("broker stopped: {err}");
+                }
+            });
+            return Ok(());
+        }
+
+        bail!("failed to start broker after {MAX_TEST_BIND_ATTEMPTS} attempts")
+    }
+
+    /// Start a local marshal using `discovery_endpoint`, returning the endpoint clients should
+    /// connect to. Picks a new port and tries again if it fails to bind, as another test may
+    /// have raced us to it.
+    ///
+    /// # Errors
+    /// If we fail to start the marshal after `MAX_TEST_BIND_ATTEMPTS` attempts
+    async fn start_test_marshal(discovery_endpoint: &DiscoveryEndpoint) -> anyhow::Result<String> {
+        for attempt in 1..=MAX_TEST_BIND_ATTEMPTS {
+            let marshal_endpoint = format!("127.0.0.1:{}", Self::pick_test_port()?);
+            let config = MarshalConfigBuilder::default()
+                .bind_address(marshal_endpoint.clone())
+                .metrics_enabled(false)
+                .discovery_endpoint(discovery_endpoint.to_string())
+                .build()?;
+
+            // Creating the marshal binds its port
+            let marshal: Marshal<TestingDef<TYPES>> = match Marshal::new(config).await {
+                Ok(marshal) => marshal,
+                Err(err) => {
+                    warn!(
+                        "failed to start marshal \
+                         (attempt {attempt}/{MAX_TEST_BIND_ATTEMPTS}): {err}"
+                    );
+                    continue;
+                }
+            };
+
+            async_spawn(async move {
+                // Error if we stopped unexpectedly
+                if let Err(err) = marshal.start().await {
+                    error!("marshal stopped: {err}");
+                }
+            });
+            return Ok(marshal_endpoint);
+        }
+
+        bail!("failed to start marshal after {MAX_TEST_BIND_ATTEMPTS} attempts")
+    }
+
+    /// Generate n Push CDN clients, a marshal, and `test_config.num_brokers` brokers (that run
+    /// locally). Uses a `SQLite` database instead of Redis. Clients subscribe to the topics
+    /// `test_config.topics` assigns them, or the first `da_committee_size` are on the DA
+    /// committee.
+    ///
+    /// Both returned handles share one client, and both wait `test_config.send_delay` before
+    /// each outgoing send. If `test_config.receive_ordering` is set, every client receives in a
+    /// deterministic order.
+    ///
+    /// # Panics
+    /// If the OS temporary directory doesn't exist
+    #[must_use]
+    pub fn generator_with_config(
+        test_config: &PushCdnTestConfig,
+        da_committee_size: usize,
+        reliability_config: Option<Box<dyn NetworkReliability>>,
+    ) -> AsyncGenerator<(Arc<Self>, Arc<Self>)> {
+        let send_delay = test_config.send_delay;
+        let receive_ordering = test_config.receive_ordering.clone();
+        let topic_assignment = test_config
+            .topics
+            .clone()
+            .unwrap_or_else(|| TopicAssignment::first_nodes_in_da(da_committee_size));
+
+        // Shared between every client we spawn
+        let reliability_config: Option<Arc<dyn NetworkReliability>> =
+            reliability_config.map(Arc::from);
+
+        // Either shared between brokers or one per broker, depending on the config
+        let broker_keypairs: Arc<Vec<(TYPES::SignatureKey, _)>> = Arc::new(
+            (0..test_config.num_brokers)
+                .map(|broker| test_config.broker_keypair(broker))
+                .collect(),
+        );
+
+        // Get the OS temporary directory
+        let temp_dir = std::env::temp_dir();
+
+        // Create an SQLite file inside of the temporary directory
+        let discovery_endpoint = DiscoveryEndpoint::sqlite(temp_dir.join(Path::new(&format!(
+            "test-{}.sqlite",
+            StdRng::from_entropy().next_u64()
+        ))))
+        .expect("the temporary directory is not usable for discovery");
+
+        // The brokers and the marshal are started by whichever client is generated first, so
+        // that we can wait for them to bind
+        let marshal_endpoint = Arc::new(OnceCell::new());
+
+        // This function is called for each client we spawn
+        Box::pin({
+            move |node_id| {
+                // Clone these so we can pin the future
+                let marshal_endpoint = Arc::clone(&marshal_endpoint);
+                let discovery_endpoint = discovery_endpoint.clone();
+                let broker_keypairs = Arc::clone(&broker_keypairs);
+                let reliability_config = reliability_config.clone();
+                let receive_ordering = receive_ordering.clone();
+                let topics = topic_assignment.topics(node_id);
+
+                Box::pin(async move {
+                    // Start the CDN if we're the first client
+                    let marshal_endpoint = marshal_endpoint
+                        .get_or_init(|| async move {
+                            for (broker_public_key, broker_private_key) in broker_keypairs.iter() {
+                                Self::start_test_broker(
+                                    &discovery_endpoint,
+                                    broker_public_key,
+                                    broker_private_key,
+                                )
+                                .await
+                                .expect("failed to start broker");
+                            }
+
+                            Self::start_test_marshal(&discovery_endpoint)
+                                .await
+                                .expect("failed to start marshal")
+                        })
+                        .await
+                        .clone();
+
+                    // Create our client, with keys derived from our index
+                    let mut network = PushCdnNetwork::new_from_seed(
+                        marshal_endpoint,
+                        topics.iter().map(ToString::to_string).collect(),
+                        [0u8; 32],
+                        node_id,
+                        None,
+                    )
+                    .await
+                    .expect("failed to create client");
+                    network.reliability_config = reliability_config;
+                    network.send_delay = send_delay;
+                    if let Some(ordering) = receive_ordering {
+                        network = network.with_receive_ordering(ordering);
+                    }
+
+                    // Both handles share the same client (and reliability config, delay and
+                    // receive ordering)
+                    let network = Arc::new(network);
+                    (network.clone(), network)
+                })
+            }
+        })
+    }
+}
+
+#[cfg(feature = "hotshot-testing")]
+impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for PushCdnNetwork<TYPES> {
+    /// Generate n Push CDN clients, a marshal, and two brokers (that run locally).
+    /// Uses a `SQLite` database instead of Redis.
+    ///
+    /// `secondary_network_delay` is applied to every outgoing send on both of the returned
+    /// handles, which share one client. A zero delay sends immediately.
+    fn generator(
+        _expected_node_count: usize,
+        _num_bootstrap: usize,
+        _network_id: usize,
+        da_committee_size: usize,
+        _is_da: bool,
+        reliability_config: Option<Box<dyn NetworkReliability>>,
+        secondary_network_delay: Duration,
+    ) -> AsyncGenerator<(Arc<Self>, Arc<Self>)> {
+        Self::generator_with_config(
+            &PushCdnTestConfig {
+                send_delay: secondary_network_delay,
+                ..PushCdnTestConfig::default()
+            },
+            da_committee_size,
+            reliability_config,
+        )
+    }
+
+    /// Get the number of sends that have been dispatched but have not yet resolved
+    fn in_flight_message_count(&self) -> Option<usize> {
+        Some(self.in_flight_message_count.load(Ordering::Relaxed))
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+    for PushCdnNetwork<TYPES>
+{
+    /// Pause sending and receiving on the PushCDN network, e.g. to quiesce a node during
+    /// maintenance. While paused, sends are dropped (but still return `Ok(())`) and received
+    /// messages are discarded.
+    fn pause(&self) {
+        self.is_paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumse sending and receiving on the PushCDN network.
+    fn resume(&self) {
+        self.is_paused.store(false, Ordering::Relaxed);
+    }
+
+    /// The clients form an initial connection when created, so this returns immediately
+    /// unless we have since lost it. Otherwise, pings until we are connected again (or have
+    /// been shut down).
+    async fn wait_for_ready(&self) {
+        while !self.is_ready().await {
+            if self.is_shut_down().await {
+                return;
+            }
+            if self.ping().await.is_err() {
+                async_sleep(self.retry_policy.delay(0)).await;
+            }
+        }
+    }
+
+    /// Whether we were connected the last time we sent, received, or pinged, and have not
+    /// been shut down.
+    async fn is_ready(&self) -> bool {
+        self.connection_state.is_connected() && !self.is_shut_down().await
+    }
+
+    /// Wait (up to a bound) for in-flight sends to resolve, then close the underlying client
+    /// and cancel the receive task. Subsequent sends and receives return
+    /// `NetworkError::ShutDown`.
+    fn shut_down<'a, 'b>(&'a self) -> BoxSyncFuture<'b, ()>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        boxed_sync(async move {
+            // Give sends we already made a chance to go out, but don't hang on a dead broker
+            if self.flush(SHUTDOWN_FLUSH_TIMEOUT).await.is_err() {
+                warn!(
+                    "Shutting down with {} sends still in flight",
+                    self.in_flight_message_count.load(Ordering::Relaxed)
+                );
+            }
+
+            // Drop our handle to the client, so nobody can send with it anymore
+            self.client.write().await.take();
+            self.connection_state.set_connected(false);
+
+            // Drop the receive task (and its handle to the client)
+            self.cancel_receive_task().await;
+
+            // The keepalive task would notice we're shut down at its next ping, but don't wait
+            let keepalive_task = self
+                .keepalive_task
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            if let Some(task) = keepalive_task {
+                cancel_task(task).await;
+            }
+
+            // Drop any sends still waiting
+            if let Some(queue) = &self.send_queue {
+                queue.shut_down().await;
+            }
+        })
+    }
+
+    /// Broadcast a message to all members of the quorum, or directly to `recipients` if there
+    /// are only a few of them.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message
+    /// - If we fail to send the broadcast message.
+    async fn broadcast_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        self.broadcast_message(message, recipients, Topic::Global, bind_version)
+            .await
+    }
+
+    /// Broadcast a message to all members of the DA committee, or directly to `recipients` if
+    /// there are only a few of them.
+    ///
+    /// # Errors
+    /// - If we fail to serialize the message
+    /// - If we fail to send the broadcast message.
+    async fn da_broadcast_message<Ver: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: Ver,
+    ) -> Result<(), NetworkError> {
+        self.broadcast_message(message, recipients, Topic::DA, bind_version)
+            .await
+    }
+
+    /// Send a direct message to a node with a particular key. Retries according to our
+    /// retry policy.
+    ///
+    /// - If we have been shut down
+    /// - If we fail to serialize the message
+    /// - If we fail to send the direct message after exhausting our retries
+    async fn direct_message<Ver: StaticVersionType>(
+        &self,
+        message: Message<TYPES>,
+        recipient: TYPES::SignatureKey,
+        _: Ver,
+    ) -> Result<(), NetworkError> {
+        // If we're paused, don't send the message
+        if self.is_paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // If we're standing in for a secondary network, hold the message back
+        #[cfg(feature = "hotshot-testing")]
+        self.delay_send().await;
+
+        // Bincode the message
+        let serialized_message = self.serialize::<Ver>(&message)?;
+
+        self.send_direct(
+            serialized_message,
+            WrappedSignatureKey(recipient),
+            message.purpose(),
+        )
+        .await
+    }
+
+    /// Receive a batch of messages: waits for one, then also takes up to `max_batch` that are
+    /// already available. Is agnostic over `transmit_type`, which has an issue to be removed
+    /// anyway.
+    ///
+    /// If we fail to receive a message, we first try to reconnect to the marshal according to
+    /// our reconnect policy, and carry on receiving if that succeeds. If we have a receive
+    /// timeout and nothing arrives within it, returns an empty batch.
+    ///
+    /// # Errors
+    /// - If we fail to receive messages and can't reconnect. Will trigger a retry automatically.
+    /// - If the receive task has died. This is terminal: the network must be rebuilt.
+    /// - If we have been shut down
+    /// - If we fail to deserialize every message in the batch. Messages serialized with a
+    ///   version newer than any we support are skipped instead, with a warning now and then.
+    ///
+    /// In testing builds, messages may instead be received in a deterministic order (see
+    /// [`PushCdnNetwork::with_receive_ordering`]).
+    async fn recv_msgs(&self) -> Result<Vec<Message<TYPES>>, NetworkError> {
+        Ok(self
+            .recv_msgs_with_meta()
+            .await?
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect())
+    }
+
+    /// We don't need to look up nodes, so just hold on to the lookup (dropping the oldest if we
+    /// have too many) until it is taken with `drain_queued_lookups`.
+    async fn queue_node_lookup(
+        &self,
+        view_number: ViewNumber,
+        pk: TYPES::SignatureKey,
+    ) -> Result<(), UnboundedSendError<Option<(ViewNumber, TYPES::SignatureKey)>>> {
+        let mut queued_lookups = self
+            .queued_lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if queued_lookups.len() >= MAX_QUEUED_LOOKUPS {
+            queued_lookups.pop_front();
+        }
+        queued_lookups.push_back((view_number, pk));
+
+        Ok(())
+    }
+
+    /// We don't need to poll.
+    async fn inject_consensus_info(&self, _event: ConsensusIntentEvent<TYPES::SignatureKey>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hotshot_types::signature_key::BLSPubKey;
+
+    /// The message we sign in each test
+    const MESSAGE: &[u8] = b"hello, world";
+
+    /// Generate a keypair from a fixed seed
+    fn keypair() -> (
+        WrappedSignatureKey<BLSPubKey>,
+        <BLSPubKey as SignatureKey>::PrivateKey,
+    ) {
+        let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+        (WrappedSignatureKey(public_key), private_key)
+    }
+
+    #[test]
+    fn topics_parse_from_their_names() {
+        for topic in KNOWN_TOPICS {
+            assert_eq!(parse_topic(&topic.to_string()).unwrap(), topic);
+        }
+
+        let err = parse_topic("bogus").unwrap_err();
+        assert!(matches!(
+            &err,
+            PushCdnNetworkError::UnknownTopic { topic, .. } if topic == "bogus"
+        ));
+        let message = err.to_string();
+        for topic in KNOWN_TOPICS {
+            assert!(message.contains(&topic.to_string()), "{message}");
+        }
+    }
+
+    #[test]
+    fn canonical_signature_round_trip() {
+        let (public_key, private_key) = keypair();
+
+        let signature = WrappedSignatureKey::<BLSPubKey>::sign(&private_key, MESSAGE)
+            .expect("failed to sign message");
+        assert_eq!(signature[0], CANONICAL_SIGNATURE_VERSION);
+        assert!(WrappedSignatureKey::verify(
+            &public_key,
+            MESSAGE,
+            &signature
+        ));
+        assert!(!WrappedSignatureKey::verify(
+            &public_key,
+            b"goodbye, world",
+            &signature
+        ));
+    }
+
+    #[test]
+    fn legacy_signature_still_verifies() {
+        let (public_key, private_key) = keypair();
+
+        let signature = BLSPubKey::sign(&private_key, MESSAGE).expect("failed to sign message");
+        let legacy = bincode_opts()
+            .serialize(&signature)
+            .expect("failed to serialize signature");
+        assert!(WrappedSignatureKey::verify(&public_key, MESSAGE, &legacy));
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        let (public_key, private_key) = keypair();
+
+        let signature = WrappedSignatureKey::<BLSPubKey>::sign(&private_key, MESSAGE)
+            .expect("failed to sign message");
+
+        // Truncated
+        assert!(
+            decode_signature::<<BLSPubKey as SignatureKey>::PureAssembledSignatureType>(
+                &signature[..signature.len() - 1]
+            )
+            .is_err()
+        );
+        assert!(!WrappedSignatureKey::verify(
+            &public_key,
+            MESSAGE,
+            &signature[..signature.len() - 1]
+        ));
+
+        // Unknown version
+        let mut unknown_version = signature.clone();
+        unknown_version[0] = CANONICAL_SIGNATURE_VERSION + 1;
+        assert!(
+            decode_signature::<<BLSPubKey as SignatureKey>::PureAssembledSignatureType>(
+                &unknown_version
+            )
+            .is_err()
+        );
+
+        // Empty
+        assert!(!WrappedSignatureKey::verify(&public_key, MESSAGE, &[]));
+    }
+
+    #[test]
+    fn only_outstanding_pings_are_answered() {
+        let pings = OutstandingPings::default();
+        let mut pending = pings.start();
+
+        // A ping we never sent, or one we've already had back, is dropped
+        assert!(!pings.answer(pending.nonce.wrapping_add(1)));
+        assert!(pings.answer(pending.nonce));
+        assert!(!pings.answer(pending.nonce));
+        assert_eq!(pending.answered.try_recv(), Ok(Some(())));
+
+        // We stop waiting on a ping once we give up on it
+        let nonce = pings.start().nonce;
+        assert!(!pings.answer(nonce));
+        drop(pending);
+        assert!(pings.waiting.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn oversized_signature_is_rejected() {
+        let (public_key, _) = keypair();
+
+        // A legacy signature whose length prefix claims far more data than we'd ever accept
+        let mut forged = u64::MAX.to_le_bytes().to_vec();
+        forged.extend_from_slice(&[0u8; 64]);
+        let start = Instant::now();
+        assert!(!WrappedSignatureKey::verify(&public_key, MESSAGE, &forged));
+        assert!(bincode_opts()
+            .with_limit(MAX_SIGNATURE_SIZE as u64)
+            .deserialize::<Vec<u8>>(&forged)
+            .is_err());
+
+        // A canonical signature whose length prefix is over the limit
+        let mut forged = vec![CANONICAL_SIGNATURE_VERSION];
+        forged.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(
+            decode_signature::<<BLSPubKey as SignatureKey>::PureAssembledSignatureType>(&forged)
+                .is_err()
+        );
+
+        // A signature that is simply too long
+        let forged = vec![0u8; MAX_SIGNATURE_SIZE + 1];
+        assert!(!WrappedSignatureKey::verify(&public_key, MESSAGE, &forged));
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn send_errors_are_classified() {
+        let err = classify_send_error(CdnError::Connection("broker went away".to_string()));
+        assert!(matches!(
+            &err,
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::ConnectionLost { reason }
+            } if reason == "broker went away"
+        ));
+        assert!(RetryPolicy::is_retryable(&err));
+
+        assert!(RetryPolicy::is_retryable(&NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::SendTimeout
+        }));
+        assert!(!RetryPolicy::is_retryable(&NetworkError::CouldNotDeliver));
+
+        // Marshal rejections are handled by authenticating again, not by retrying as-is
+        let err = classify_send_error(CdnError::Authentication("bad signature".to_string()));
+        assert!(matches!(
+            &err,
+            NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::AuthenticationFailed { reason }
+            } if reason == "bad signature"
+        ));
+        assert!(RetryPolicy::needs_reauthentication(&err));
+        assert!(!RetryPolicy::is_retryable(&err));
+    }
+
+    #[test]
+    fn serialization_errors_name_the_message() {
+        let NetworkError::FailedToSerialize { source } = serialization_error(
+            MessagePurpose::Data,
+            7,
+            anyhow::anyhow!("sequence too long"),
+        ) else {
+            panic!("expected a serialization error");
+        };
+        assert_eq!(
+            source.to_string(),
+            "failed to serialize Data message for view 7"
+        );
+        assert_eq!(source.root_cause().to_string(), "sequence too long");
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::Instant;
+
+use super::MessageOrigin;
+
+/// An allowance of messages: up to `burst` back to back, refilled at `per_second`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// How many messages may arrive back to back
+    pub burst: u32,
+    /// How many messages per second may arrive once the burst is used up
+    pub per_second: f64,
+}
+
+/// How fast we accept inbound messages, so that a flood can't saturate decoding and
+/// consensus. The CDN doesn't tell us who sent a message, and the sender named inside one is
+/// unauthenticated, so messages are limited by how they reached us rather than by who claims
+/// to have sent them. All direct messages share one allowance and all broadcasts another, so
+/// that legitimate broadcast traffic isn't held to the stricter direct limit. A flood still
+/// crowds out other messages arriving the same way. See
+/// [`PushCdnNetwork::with_inbound_rate_limit`](super::PushCdnNetwork::with_inbound_rate_limit).
+#[derive(Clone, Debug)]
+pub struct InboundRateLimit {
+    /// The allowance shared by all direct messages
+    pub direct: RateLimit,
+    /// The allowance shared by all broadcasts
+    pub broadcast: RateLimit,
+}
+
+impl Default for InboundRateLimit {
+    fn default() -> Self {
+        Self {
+            direct: RateLimit {
+                burst: 1024,
+                per_second: 1024.0,
+            },
+            broadcast: RateLimit {
+                burst: 8192,
+                per_second: 8192.0,
+            },
+        }
+    }
+}
+
+/// What is left of an allowance
+struct TokenBucket {
+    /// How many messages may still arrive right away
+    tokens: f64,
+    /// When `tokens` was last brought up to date
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// A bucket with all of `limit`'s burst available at `now`
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            refilled: now,
+        }
+    }
+
+    /// Take a message from the allowance at `now`, refilling at `limit` first. Returns whether
+    /// there was one to take.
+    fn take(&mut self, limit: RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(f64::from(limit.burst));
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// The remaining allowances for direct messages and broadcasts
+pub(super) struct InboundRateLimiter {
+    /// The allowances
+    config: InboundRateLimit,
+    /// What is left of the direct message allowance
+    direct: TokenBucket,
+    /// What is left of the broadcast allowance
+    broadcast: TokenBucket,
+}
+
+impl InboundRateLimiter {
+    /// Start with full allowances under `config` at `now`
+    pub(super) fn new(config: InboundRateLimit, now: Instant) -> Self {
+        Self {
+            direct: TokenBucket::full(config.direct, now),
+            broadcast: TokenBucket::full(config.broadcast, now),
+            config,
+        }
+    }
+
+    /// Whether to accept a message that reached us by `origin` at `now`. If so, it is taken
+    /// from that origin's allowance.
+    pub(super) fn admit(&mut self, origin: &MessageOrigin, now: Instant) -> bool {
+        match origin {
+            MessageOrigin::Broadcast(_) => self.broadcast.take(self.config.broadcast, now),
+            MessageOrigin::Direct => self.direct.take(self.config.direct, now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdn_client::reexports::message::Topic;
+    use std::time::Duration;
+
+    #[test]
+    fn inbound_rate_limit_is_per_origin() {
+        let start = Instant::now();
+        let mut limiter = InboundRateLimiter::new(
+            InboundRateLimit {
+                direct: RateLimit {
+                    burst: 2,
+                    per_second: 1.0,
+                },
+                broadcast: RateLimit {
+                    burst: 4,
+                    per_second: 1.0,
+                },
+            },
+            start,
+        );
+        let broadcast = MessageOrigin::Broadcast(vec![Topic::Global]);
+
+        // A flood of direct messages gets the burst and no more
+        assert_eq!(
+            (0..8)
+                .filter(|_| limiter.admit(&MessageOrigin::Direct, start))
+                .count(),
+            2
+        );
+
+        // Broadcasts have their own allowance
+        assert_eq!(
+            (0..8).filter(|_| limiter.admit(&broadcast, start)).count(),
+            4
+        );
+
+        // The allowance refills over time
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.admit(&MessageOrigin::Direct, later));
+        assert!(!limiter.admit(&MessageOrigin::Direct, later));
+    }
+}
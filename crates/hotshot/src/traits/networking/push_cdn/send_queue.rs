@@ -0,0 +1,285 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, PoisonError,
+};
+
+use async_compatibility_layer::art::async_spawn;
+#[cfg(async_executor_impl = "async-std")]
+use async_std::task::JoinHandle;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::BoxFuture,
+    select_biased, StreamExt,
+};
+use hotshot_task_impls::helpers::cancel_task;
+use hotshot_types::{message::MessagePurpose, traits::network::PushCdnNetworkError};
+#[cfg(async_executor_impl = "tokio")]
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::NetworkError;
+
+/// How urgently an outgoing message needs to reach the CDN, when sends are queued
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendPriority {
+    /// Sent before any bulk message that is still waiting
+    Urgent,
+    /// Sent once no urgent message is waiting
+    Bulk,
+}
+
+/// How outgoing sends are queued, so that a slow broadcast doesn't hold up an urgent message
+/// sent after it. See
+/// [`PushCdnNetwork::with_send_queue`](super::PushCdnNetwork::with_send_queue).
+#[derive(Clone, Debug)]
+pub struct SendQueueConfig {
+    /// The most sends that may be waiting at once. Sends beyond this are rejected, so that
+    /// callers feel the backpressure instead of queueing without bound.
+    pub max_depth: usize,
+    /// Whether direct messages are urgent, whatever their purpose
+    pub direct_is_urgent: bool,
+    /// The purposes of the messages that are urgent, however they are sent. Every other
+    /// message is bulk, unless it is direct and `direct_is_urgent` is set.
+    pub urgent_purposes: Vec<MessagePurpose>,
+}
+
+impl Default for SendQueueConfig {
+    /// Votes (quorum, DA, and timeout) and view sync traffic are urgent, since consensus waits
+    /// on them to make progress, whether they are sent to the leader or broadcast. Everything
+    /// else, including direct VID shares, is bulk.
+    fn default() -> Self {
+        Self {
+            max_depth: 1024,
+            direct_is_urgent: false,
+            urgent_purposes: vec![
+                MessagePurpose::Vote,
+                MessagePurpose::ViewSyncVote,
+                MessagePurpose::ViewSyncCertificate,
+            ],
+        }
+    }
+}
+
+impl SendQueueConfig {
+    /// The priority of a message with `purpose`, sent directly or broadcast
+    #[must_use]
+    pub fn priority(&self, purpose: MessagePurpose, direct: bool) -> SendPriority {
+        if (direct && self.direct_is_urgent) || self.urgent_purposes.contains(&purpose) {
+            SendPriority::Urgent
+        } else {
+            SendPriority::Bulk
+        }
+    }
+}
+
+/// A send waiting in the send queue, and who to tell how it went
+type QueuedSend = (
+    BoxFuture<'static, Result<(), NetworkError>>,
+    oneshot::Sender<Result<(), NetworkError>>,
+);
+
+/// The queue outgoing sends wait in, drained to the CDN one at a time, urgent sends first, by a
+/// dedicated task
+pub(super) struct SendQueue {
+    /// How we prioritize and bound sends
+    pub(super) config: SendQueueConfig,
+    /// Where urgent sends wait
+    urgent: mpsc::UnboundedSender<QueuedSend>,
+    /// Where bulk sends wait
+    bulk: mpsc::UnboundedSender<QueuedSend>,
+    /// The number of sends queued or being sent
+    depth: Arc<AtomicUsize>,
+    /// The task draining the queue. Taken when we are shut down.
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SendQueue {
+    /// Create an empty queue, and spawn the task that drains it
+    pub(super) fn spawn(config: SendQueueConfig) -> Self {
+        let (urgent, mut urgent_sends) = mpsc::unbounded::<QueuedSend>();
+        let (bulk, mut bulk_sends) = mpsc::unbounded::<QueuedSend>();
+        let depth = Arc::new(AtomicUsize::new(0));
+
+        let task_depth = Arc::clone(&depth);
+        let task = async_spawn(async move {
+            loop {
+                let next = select_biased! {
+                    send = urgent_sends.next() => send,
+                    send = bulk_sends.next() => send,
+                };
+                let Some((send, done)) = next else {
+                    break;
+                };
+
+                let result = send.await;
+                task_depth.fetch_sub(1, Ordering::Relaxed);
+                let _ = done.send(result);
+            }
+        });
+
+        Self {
+            config,
+            urgent,
+            bulk,
+            depth,
+            task: Mutex::new(Some(task)),
+        }
+    }
+
+    /// Queue `send` behind every send of at least its priority, and wait for it to be sent
+    ///
+    /// # Errors
+    /// - If `send` fails
+    /// - `PushCdnNetworkError::SendQueueFull` if the queue is full
+    /// - If the queue was shut down before `send` was sent
+    pub(super) async fn send(
+        &self,
+        priority: SendPriority,
+        send: BoxFuture<'static, Result<(), NetworkError>>,
+    ) -> Result<(), NetworkError> {
+        let max_depth = self.config.max_depth;
+        if self
+            .depth
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+                (depth < max_depth).then_some(depth + 1)
+            })
+            .is_err()
+        {
+            warn!("send queue is full, rejecting a {priority:?} send");
+            return Err(NetworkError::PushCdnNetwork {
+                source: PushCdnNetworkError::SendQueueFull { max_depth },
+            });
+        }
+
+        let (done, result) = oneshot::channel();
+        let queue = match priority {
+            SendPriority::Urgent => &self.urgent,
+            SendPriority::Bulk => &self.bulk,
+        };
+        if queue.unbounded_send((send, done)).is_err() {
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+            return Err(NetworkError::ShutDown);
+        }
+
+        // The task drops the send without answering if it is cancelled
+        result.await.unwrap_or(Err(NetworkError::ShutDown))
+    }
+
+    /// Stop draining the queue, dropping every send still waiting
+    pub(super) async fn shut_down(&self) {
+        let task = self
+            .task
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(task) = task {
+            cancel_task(task).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+
+    #[test]
+    fn send_priorities_follow_the_config() {
+        // By default, urgency depends on what a message is, not how it is sent
+        let config = SendQueueConfig::default();
+        for direct in [true, false] {
+            assert_eq!(
+                config.priority(MessagePurpose::Vote, direct),
+                SendPriority::Urgent
+            );
+            assert_eq!(
+                config.priority(MessagePurpose::ViewSyncCertificate, direct),
+                SendPriority::Urgent
+            );
+            assert_eq!(
+                config.priority(MessagePurpose::VidDisperse, direct),
+                SendPriority::Bulk
+            );
+        }
+
+        let config = SendQueueConfig {
+            direct_is_urgent: true,
+            ..SendQueueConfig::default()
+        };
+        assert_eq!(
+            config.priority(MessagePurpose::Proposal, true),
+            SendPriority::Urgent
+        );
+        assert_eq!(
+            config.priority(MessagePurpose::Proposal, false),
+            SendPriority::Bulk
+        );
+
+        let config = SendQueueConfig {
+            urgent_purposes: vec![MessagePurpose::Proposal],
+            ..SendQueueConfig::default()
+        };
+        assert_eq!(
+            config.priority(MessagePurpose::Vote, true),
+            SendPriority::Bulk
+        );
+        assert_eq!(
+            config.priority(MessagePurpose::Proposal, false),
+            SendPriority::Urgent
+        );
+    }
+
+    /// Urgent sends overtake the bulk sends still waiting, a full queue rejects sends, and a
+    /// queue that was shut down sends nothing
+    #[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+    #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+    async fn send_queue_prioritizes_and_bounds_sends() {
+        let queue = SendQueue::spawn(SendQueueConfig {
+            max_depth: 3,
+            ..SendQueueConfig::default()
+        });
+        let sent = Arc::new(Mutex::new(Vec::new()));
+
+        // A send that records `name` as sent, once `gate` (if any) opens
+        let send = |name: &'static str, gate: Option<oneshot::Receiver<()>>| {
+            let sent = Arc::clone(&sent);
+            let send: BoxFuture<'static, Result<(), NetworkError>> = Box::pin(async move {
+                if let Some(gate) = gate {
+                    let _ = gate.await;
+                }
+                sent.lock().unwrap().push(name);
+                Ok(())
+            });
+            send
+        };
+
+        // The first send holds up the queue until we open the gate
+        let (open, gate) = oneshot::channel();
+        let sends = join_all([
+            queue.send(SendPriority::Urgent, send("first", Some(gate))),
+            queue.send(SendPriority::Bulk, send("bulk", None)),
+            queue.send(SendPriority::Urgent, send("urgent", None)),
+        ]);
+        let fill_then_open = async {
+            assert!(matches!(
+                queue
+                    .send(SendPriority::Urgent, send("rejected", None))
+                    .await,
+                Err(NetworkError::PushCdnNetwork {
+                    source: PushCdnNetworkError::SendQueueFull { max_depth: 3 }
+                })
+            ));
+            let _ = open.send(());
+        };
+        let (results, ()) = futures::join!(sends, fill_then_open);
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(*sent.lock().unwrap(), vec!["first", "urgent", "bulk"]);
+
+        queue.shut_down().await;
+        assert!(matches!(
+            queue.send(SendPriority::Bulk, send("late", None)).await,
+            Err(NetworkError::ShutDown)
+        ));
+    }
+}
@@ -0,0 +1,553 @@
+//! Networks for turning a live run into a deterministic test. [`RecordingNetwork`] wraps any
+//! network and records every message it sends and receives to a [`TrafficSink`], and
+//! [`ReplayNetwork`] feeds the received messages of such a recording back through `recv_msgs`,
+//! without any real network underneath.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+    sync::{
+        mpsc::{
+            channel as std_channel, Receiver as StdReceiver, Sender as StdSender, TryRecvError,
+        },
+        Arc, Mutex as StdMutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use async_compatibility_layer::channel::UnboundedSendError;
+use async_lock::Mutex;
+use async_trait::async_trait;
+use bincode::{ErrorKind as BincodeErrorKind, Options};
+use futures::{
+    channel::{mpsc, oneshot},
+    StreamExt,
+};
+use hotshot_types::{
+    boxed_sync,
+    data::ViewNumber,
+    message::Message,
+    traits::{
+        network::{
+            ConnectedNetwork, ConsensusIntentEvent, NetworkError, ResponseChannel, ResponseMessage,
+        },
+        node_implementation::NodeType,
+    },
+    utils::bincode_opts,
+    BoxSyncFuture,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use versioned_binary_serialization::version::StaticVersionType;
+
+/// How a recorded message was sent, or that it was received
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "", serialize = ""))]
+pub enum TrafficKind<TYPES: NodeType> {
+    /// Broadcast to everyone, or to `recipients`
+    Broadcast {
+        /// The recipients the broadcast was addressed to
+        recipients: BTreeSet<TYPES::SignatureKey>,
+    },
+    /// Broadcast to the DA committee
+    DaBroadcast {
+        /// The recipients the broadcast was addressed to
+        recipients: BTreeSet<TYPES::SignatureKey>,
+    },
+    /// Sent directly to `recipient`
+    Direct {
+        /// The node the message was sent to
+        recipient: TYPES::SignatureKey,
+    },
+    /// Received from the network
+    Received,
+}
+
+/// A message that went through a [`RecordingNetwork`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "", serialize = ""))]
+pub struct TrafficEvent<TYPES: NodeType> {
+    /// How long after recording started the message went by
+    pub elapsed: Duration,
+    /// Which way the message went, and how
+    pub kind: TrafficKind<TYPES>,
+    /// The message itself
+    pub message: Message<TYPES>,
+}
+
+/// Where a [`RecordingNetwork`] puts what it records
+pub trait TrafficSink<TYPES: NodeType>: Send + Sync + 'static {
+    /// Record `event`. Recording must not fail the network, so sinks deal with their own errors.
+    fn record(&self, event: TrafficEvent<TYPES>);
+}
+
+/// A [`TrafficSink`] that keeps everything in memory
+pub struct MemorySink<TYPES: NodeType> {
+    /// Every event recorded so far, oldest first
+    events: StdMutex<Vec<TrafficEvent<TYPES>>>,
+}
+
+impl<TYPES: NodeType> Default for MemorySink<TYPES> {
+    fn default() -> Self {
+        Self {
+            events: StdMutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<TYPES: NodeType> MemorySink<TYPES> {
+    /// Get every event recorded so far, oldest first
+    pub fn events(&self) -> Vec<TrafficEvent<TYPES>> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl<TYPES: NodeType> TrafficSink<TYPES> for MemorySink<TYPES> {
+    fn record(&self, event: TrafficEvent<TYPES>) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+    }
+}
+
+/// What the writer thread of a [`FileSink`] is asked to do
+enum FileSinkCommand {
+    /// Append an event, already serialized
+    Write(Vec<u8>),
+    /// Say when everything asked before this has been written and flushed
+    Flush(oneshot::Sender<()>),
+}
+
+/// A [`TrafficSink`] that appends events to a file, to be read back with [`read_recording`].
+/// Events are serialized as they are recorded and written by a thread of their own, so
+/// recording never waits on the file.
+pub struct FileSink {
+    /// Hands events to the writer thread
+    commands: StdMutex<StdSender<FileSinkCommand>>,
+}
+
+impl FileSink {
+    /// Create (or truncate) the file at `path` and start recording to it
+    ///
+    /// # Errors
+    /// If we fail to create the file, or to start the thread that writes to it
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("failed to create recording {}", path.display()))?;
+
+        let (commands, pending) = std_channel();
+        thread::Builder::new()
+            .name("recording-writer".to_string())
+            .spawn(move || write_recording(BufWriter::new(file), &pending))
+            .context("failed to start recording writer")?;
+
+        Ok(Self {
+            commands: StdMutex::new(commands),
+        })
+    }
+
+    /// Wait until every event recorded so far has been written to the file and flushed (or
+    /// failed to be, which is logged)
+    ///
+    /// # Errors
+    /// If the writer thread has stopped
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.send(FileSinkCommand::Flush(done))?;
+        flushed
+            .await
+            .map_err(|_| anyhow!("recording writer stopped"))
+    }
+
+    /// Hand `command` to the writer thread
+    ///
+    /// # Errors
+    /// If the writer thread has stopped
+    fn send(&self, command: FileSinkCommand) -> anyhow::Result<()> {
+        self.commands
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(command)
+            .map_err(|_| anyhow!("recording writer stopped"))
+    }
+}
+
+impl<TYPES: NodeType> TrafficSink<TYPES> for FileSink {
+    fn record(&self, event: TrafficEvent<TYPES>) {
+        let result = bincode_opts()
+            .serialize(&event)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| self.send(FileSinkCommand::Write(bytes)));
+        if let Err(err) = result {
+            warn!("failed to record network traffic: {err}");
+        }
+    }
+}
+
+/// Write what a [`FileSink`] sends to `commands` to `file`, until the sink is dropped. The file
+/// is flushed whenever we catch up, so a recording survives the process crashing without
+/// flushing after every event.
+fn write_recording(mut file: BufWriter<File>, commands: &StdReceiver<FileSinkCommand>) {
+    // Who is waiting for the next flush
+    let mut waiting = Vec::new();
+    loop {
+        let command = match commands.try_recv() {
+            Ok(command) => command,
+            Err(TryRecvError::Empty) => {
+                flush_recording(&mut file, &mut waiting);
+                let Ok(command) = commands.recv() else {
+                    break;
+                };
+                command
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+
+        match command {
+            FileSinkCommand::Write(bytes) => {
+                if let Err(err) = file.write_all(&bytes) {
+                    warn!("failed to record network traffic: {err}");
+                }
+            }
+            FileSinkCommand::Flush(done) => waiting.push(done),
+        }
+    }
+    flush_recording(&mut file, &mut waiting);
+}
+
+/// Flush `file`, then tell everyone `waiting` that we have
+fn flush_recording(file: &mut BufWriter<File>, waiting: &mut Vec<oneshot::Sender<()>>) {
+    if let Err(err) = file.flush() {
+        warn!("failed to flush recorded network traffic: {err}");
+    }
+    for done in waiting.drain(..) {
+        let _ = done.send(());
+    }
+}
+
+/// Read back every event a [`FileSink`] recorded to the file at `path`, oldest first
+///
+/// # Errors
+/// - If we fail to open the file
+/// - If the file contains anything but whole recorded events
+pub fn read_recording<TYPES: NodeType>(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<Vec<TrafficEvent<TYPES>>> {
+    let path = path.as_ref();
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open recording {}", path.display()))?,
+    );
+
+    let mut events = Vec::new();
+    loop {
+        match bincode_opts().deserialize_from(&mut file) {
+            Ok(event) => events.push(event),
+            // The file ends between events
+            Err(err) if is_end_of_file(&err) => return Ok(events),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read event {} of recording", events.len()))
+            }
+        }
+    }
+}
+
+/// Whether `err` came from running out of file to read
+fn is_end_of_file(err: &bincode::Error) -> bool {
+    matches!(&**err, BincodeErrorKind::Io(err) if err.kind() == ErrorKind::UnexpectedEof)
+}
+
+/// A network that records every message sent and received through an inner network to a
+/// [`TrafficSink`]. Sends are recorded once they succeed.
+#[derive(Clone)]
+pub struct RecordingNetwork<TYPES: NodeType, N> {
+    /// The network that actually sends and receives
+    inner: N,
+    /// Where we record traffic to
+    sink: Arc<dyn TrafficSink<TYPES>>,
+    /// When we started recording, which event timestamps are relative to
+    start: Instant,
+}
+
+impl<TYPES: NodeType, N> RecordingNetwork<TYPES, N> {
+    /// Wrap `inner`, recording its traffic to `sink` from now on
+    pub fn new(inner: N, sink: Arc<dyn TrafficSink<TYPES>>) -> Self {
+        Self {
+            inner,
+            sink,
+            start: Instant::now(),
+        }
+    }
+
+    /// Get the network we are recording
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    /// Record that `message` went by
+    fn record(&self, kind: TrafficKind<TYPES>, message: Message<TYPES>) {
+        self.sink.record(TrafficEvent {
+            elapsed: self.start.elapsed(),
+            kind,
+            message,
+        });
+    }
+
+    /// Record `message` as sent with `kind` if `result` says it was
+    fn record_sent(
+        &self,
+        result: Result<(), NetworkError>,
+        kind: TrafficKind<TYPES>,
+        message: Message<TYPES>,
+    ) -> Result<(), NetworkError> {
+        if result.is_ok() {
+            self.record(kind, message);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType, N: ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>>
+    ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey> for RecordingNetwork<TYPES, N>
+{
+    fn pause(&self) {
+        self.inner.pause();
+    }
+
+    fn resume(&self) {
+        self.inner.resume();
+    }
+
+    async fn wait_for_ready(&self) {
+        self.inner.wait_for_ready().await;
+    }
+
+    async fn is_ready(&self) -> bool {
+        self.inner.is_ready().await
+    }
+
+    fn shut_down<'a, 'b>(&'a self) -> BoxSyncFuture<'b, ()>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        self.inner.shut_down()
+    }
+
+    async fn broadcast_message<VER: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        let result = self
+            .inner
+            .broadcast_message(message.clone(), recipients.clone(), bind_version)
+            .await;
+        self.record_sent(result, TrafficKind::Broadcast { recipients }, message)
+    }
+
+    async fn da_broadcast_message<VER: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipients: BTreeSet<TYPES::SignatureKey>,
+        bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        let result = self
+            .inner
+            .da_broadcast_message(message.clone(), recipients.clone(), bind_version)
+            .await;
+        self.record_sent(result, TrafficKind::DaBroadcast { recipients }, message)
+    }
+
+    async fn direct_message<VER: StaticVersionType + 'static>(
+        &self,
+        message: Message<TYPES>,
+        recipient: TYPES::SignatureKey,
+        bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        let result = self
+            .inner
+            .direct_message(message.clone(), recipient.clone(), bind_version)
+            .await;
+        self.record_sent(result, TrafficKind::Direct { recipient }, message)
+    }
+
+    async fn recv_msgs(&self) -> Result<Vec<Message<TYPES>>, NetworkError> {
+        let messages = self.inner.recv_msgs().await?;
+        for message in &messages {
+            self.record(TrafficKind::Received, message.clone());
+        }
+        Ok(messages)
+    }
+
+    async fn request_data<T: NodeType, VER: StaticVersionType + 'static>(
+        &self,
+        request: Message<TYPES>,
+        recipient: TYPES::SignatureKey,
+        bind_version: VER,
+    ) -> Result<ResponseMessage<T>, NetworkError> {
+        self.inner
+            .request_data(request, recipient, bind_version)
+            .await
+    }
+
+    async fn spawn_request_receiver_task<VER: StaticVersionType + 'static>(
+        &self,
+        bind_version: VER,
+    ) -> Option<mpsc::Receiver<(Message<TYPES>, ResponseChannel<Message<TYPES>>)>> {
+        self.inner.spawn_request_receiver_task(bind_version).await
+    }
+
+    async fn queue_node_lookup(
+        &self,
+        view_number: ViewNumber,
+        pk: TYPES::SignatureKey,
+    ) -> Result<(), UnboundedSendError<Option<(ViewNumber, TYPES::SignatureKey)>>> {
+        self.inner.queue_node_lookup(view_number, pk).await
+    }
+
+    async fn inject_consensus_info(&self, event: ConsensusIntentEvent<TYPES::SignatureKey>) {
+        self.inner.inject_consensus_info(event).await;
+    }
+
+    fn update_view(&self, view: u64) {
+        self.inner.update_view(view);
+    }
+}
+
+/// A network that receives the messages of a recording, in the order they were recorded, and
+/// sends nowhere. Timestamps are ignored, so replays are deterministic. Once the recording runs
+/// out, `recv_msgs` waits until we are shut down, like a quiet network.
+///
+/// To check what the code under test sends, wrap this in a [`RecordingNetwork`].
+#[derive(Clone)]
+pub struct ReplayNetwork<TYPES: NodeType> {
+    /// The recorded messages we have yet to receive
+    messages: Arc<Mutex<mpsc::UnboundedReceiver<Message<TYPES>>>>,
+    /// Keeps `messages` open until we are shut down
+    open: Arc<StdMutex<Option<mpsc::UnboundedSender<Message<TYPES>>>>>,
+}
+
+impl<TYPES: NodeType> ReplayNetwork<TYPES> {
+    /// Replay the messages received in `events`. Sent messages are skipped.
+    pub fn new(events: impl IntoIterator<Item = TrafficEvent<TYPES>>) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        for event in events {
+            if event.kind == TrafficKind::Received {
+                // We hold the receiver, so this can't fail
+                let _ = sender.unbounded_send(event.message);
+            }
+        }
+
+        Self {
+            messages: Arc::new(Mutex::new(receiver)),
+            open: Arc::new(StdMutex::new(Some(sender))),
+        }
+    }
+
+    /// Replay the messages received in the recording at `path`
+    ///
+    /// # Errors
+    /// If we fail to read the recording
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self::new(read_recording(path)?))
+    }
+
+    /// Whether or not we have been shut down
+    fn is_shut_down(&self) -> bool {
+        self.open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none()
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+    for ReplayNetwork<TYPES>
+{
+    fn pause(&self) {}
+
+    fn resume(&self) {}
+
+    async fn wait_for_ready(&self) {}
+
+    async fn is_ready(&self) -> bool {
+        !self.is_shut_down()
+    }
+
+    fn shut_down<'a, 'b>(&'a self) -> BoxSyncFuture<'b, ()>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        boxed_sync(async move {
+            // Closing the channel wakes up anyone waiting to receive
+            self.open
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+        })
+    }
+
+    async fn broadcast_message<VER: StaticVersionType + 'static>(
+        &self,
+        _message: Message<TYPES>,
+        _recipients: BTreeSet<TYPES::SignatureKey>,
+        _bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        Ok(())
+    }
+
+    async fn da_broadcast_message<VER: StaticVersionType + 'static>(
+        &self,
+        _message: Message<TYPES>,
+        _recipients: BTreeSet<TYPES::SignatureKey>,
+        _bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        Ok(())
+    }
+
+    async fn vid_broadcast_message<VER: StaticVersionType + 'static>(
+        &self,
+        _messages: HashMap<TYPES::SignatureKey, Message<TYPES>>,
+        _bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        Ok(())
+    }
+
+    async fn direct_message<VER: StaticVersionType + 'static>(
+        &self,
+        _message: Message<TYPES>,
+        _recipient: TYPES::SignatureKey,
+        _bind_version: VER,
+    ) -> Result<(), NetworkError> {
+        Ok(())
+    }
+
+    /// Receive the next recorded message
+    ///
+    /// # Errors
+    /// If we have been shut down
+    async fn recv_msgs(&self) -> Result<Vec<Message<TYPES>>, NetworkError> {
+        if self.is_shut_down() {
+            return Err(NetworkError::ShutDown);
+        }
+
+        match self.messages.lock().await.next().await {
+            Some(message) if !self.is_shut_down() => Ok(vec![message]),
+            _ => Err(NetworkError::ShutDown),
+        }
+    }
+}
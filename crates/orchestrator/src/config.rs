@@ -167,6 +167,9 @@ pub struct NetworkConfig<KEY: SignatureKey, ELECTIONCONFIG: ElectionConfig> {
     pub da_web_server_config: Option<WebServerConfig>,
     /// The address for the Push CDN's "marshal", A.K.A. load balancer
     pub cdn_marshal_address: Option<String>,
+    /// The discovery endpoint shared by the Push CDN's brokers and marshal, for runs that start
+    /// the CDN themselves
+    pub cdn_discovery_endpoint: Option<String>,
     /// combined network config
     pub combined_network_config: Option<CombinedNetworkConfig>,
     /// the commit this run is based on
@@ -404,6 +407,7 @@ impl<K: SignatureKey, E: ElectionConfig> Default for NetworkConfig<K, E> {
             web_server_config: None,
             da_web_server_config: None,
             cdn_marshal_address: None,
+            cdn_discovery_endpoint: None,
             combined_network_config: None,
             next_view_timeout: 10,
             view_sync_timeout: Duration::from_secs(2),
@@ -448,6 +452,10 @@ pub struct NetworkConfigFile<KEY: SignatureKey> {
     /// The address of the Push CDN's "marshal", A.K.A. load balancer
     #[serde(default)]
     pub cdn_marshal_address: Option<String>,
+    /// The discovery endpoint shared by the Push CDN's brokers and marshal, for runs that start
+    /// the CDN themselves
+    #[serde(default)]
+    pub cdn_discovery_endpoint: Option<String>,
     /// the webserver config
     #[serde(default)]
     pub web_server_config: Option<WebServerConfig>,
@@ -499,6 +507,7 @@ impl<K: SignatureKey, E: ElectionConfig> From<NetworkConfigFile<K>> for NetworkC
             election_config_type_name: std::any::type_name::<E>().to_string(),
             start_delay_seconds: val.start_delay_seconds,
             cdn_marshal_address: val.cdn_marshal_address,
+            cdn_discovery_endpoint: val.cdn_discovery_endpoint,
             web_server_config: val.web_server_config,
             da_web_server_config: val.da_web_server_config,
             combined_network_config: val.combined_network_config,
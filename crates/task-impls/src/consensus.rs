@@ -17,7 +17,7 @@ use hotshot_types::{
     data::{Leaf, QuorumProposal},
     event::{Event, EventType},
     message::{GeneralConsensusMessage, Proposal},
-    simple_certificate::{QuorumCertificate, TimeoutCertificate, UpgradeCertificate},
+    simple_certificate::{QcCache, QuorumCertificate, TimeoutCertificate, UpgradeCertificate},
     simple_vote::{QuorumData, QuorumVote, TimeoutData, TimeoutVote},
     traits::{
         block_contents::BlockHeader,
@@ -218,6 +218,9 @@ pub struct ConsensusTaskState<
     /// Membership for Quorum Certs/votes
     pub quorum_membership: Arc<TYPES::Membership>,
 
+    /// The justify QCs we have already validated against `quorum_membership`
+    pub justify_qc_cache: QcCache<TYPES>,
+
     /// Membership for DA committee Votes/certs
     pub committee_membership: Arc<TYPES::Membership>,
 
@@ -547,7 +550,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
 
                 let justify_qc = proposal.data.justify_qc.clone();
 
-                if !justify_qc.is_valid_cert(self.quorum_membership.as_ref()) {
+                if !self
+                    .justify_qc_cache
+                    .is_valid(&justify_qc, self.quorum_membership.as_ref())
+                {
                     error!("Invalid justify_qc in proposal for view {}", *view);
                     let consensus = self.consensus.write().await;
                     consensus.metrics.invalid_qc.update(1);
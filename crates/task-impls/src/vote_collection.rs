@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData, sync::Arc};
+use std::{fmt::Debug, sync::Arc};
 
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
@@ -19,9 +19,9 @@ use hotshot_types::{
         ViewSyncPreCommitVote,
     },
     traits::{election::Membership, node_implementation::NodeType},
-    vote::{Certificate, HasViewNumber, Vote, VoteAccumulator},
+    vote::{Certificate, HasViewNumber, Vote, VoteAccumulator, VoteError},
 };
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 /// Task state for collecting votes of one type and emitting a certificate
 pub struct VoteCollectionTaskState<
@@ -88,9 +88,25 @@ impl<
         }
 
         let accumulator = self.accumulator.as_mut()?;
-        match accumulator.accumulate(vote, &self.membership) {
-            Either::Left(()) => None,
-            Either::Right(cert) => {
+        match accumulator.try_accumulate(vote, &self.membership) {
+            Ok(Either::Left(())) => None,
+            Err(VoteError::DuplicateVote) => {
+                debug!("Skipping duplicate vote for view {}", *self.view);
+                None
+            }
+            Err(VoteError::Equivocation) => {
+                warn!(
+                    "Vote from {} for view {} equivocates, accumulated it anyway",
+                    vote.get_signing_key(),
+                    *self.view
+                );
+                None
+            }
+            Err(err) => {
+                error!("Vote for view {} did not count: {err}", *self.view);
+                None
+            }
+            Ok(Either::Right(cert)) => {
                 debug!("Certificate Formed! {:?}", cert);
 
                 broadcast_event(
@@ -197,10 +213,12 @@ where
         );
         return None;
     }
-    let new_accumulator = VoteAccumulator {
-        vote_outcomes: HashMap::new(),
-        signers: HashMap::new(),
-        phantom: PhantomData,
+    let new_accumulator = match VoteAccumulator::new(&info.membership) {
+        Ok(accumulator) => accumulator,
+        Err(err) => {
+            error!("Failed to create vote accumulator: {err}");
+            return None;
+        }
     };
 
     let mut state = VoteCollectionTaskState::<TYPES, VOTE, CERT> {
@@ -1,13 +1,73 @@
-use std::time::Duration;
+use std::{
+    collections::BTreeSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use async_compatibility_layer::logging::shutdown_logging;
-use hotshot_example_types::node_types::{PushCdnImpl, TestTypes};
+use async_compatibility_layer::{
+    art::{async_sleep, async_spawn, async_timeout},
+    logging::shutdown_logging,
+};
+use futures::StreamExt;
+use hotshot::traits::implementations::{
+    deserialize_versioned, ConnectionEvent, ConnectionTuning, InboundRateLimit, MessageOrigin,
+    MockPushCdnNetwork, PushCdnNetwork, PushCdnTestConfig, RateLimit, ReceiveOrdering,
+    SendQueueConfig, Topic, TopicAssignment, MAX_QUEUED_LOOKUPS,
+};
+use hotshot_example_types::{
+    block_types::TestTransaction,
+    node_types::{PushCdnImpl, TestTypes},
+};
 use hotshot_testing::{
     completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
     overall_safety_task::OverallSafetyPropertiesDescription,
     test_builder::{TestMetadata, TimingData},
 };
+use hotshot_types::{
+    constants::{Version01, STATIC_VER_0_1},
+    data::ViewNumber,
+    message::{DataMessage, Message, MessageKind},
+    signature_key::BLSPubKey,
+    traits::{
+        network::{
+            AsyncGenerator, AsynchronousNetwork, ConnectedNetwork, NetworkError,
+            PushCdnNetworkError, TestableNetworkingImplementation,
+        },
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+    },
+};
 use tracing::instrument;
+use versioned_binary_serialization::{version::StaticVersion, BinarySerializer, Serializer};
+
+/// Spin up a local Push CDN (brokers and a marshal) and return a generator for its clients
+fn push_cdn_generator(
+    da_committee_size: usize,
+) -> AsyncGenerator<(
+    Arc<PushCdnNetwork<TestTypes>>,
+    Arc<PushCdnNetwork<TestTypes>>,
+)> {
+    <PushCdnNetwork<TestTypes> as TestableNetworkingImplementation<TestTypes>>::generator(
+        da_committee_size,
+        0,
+        0,
+        da_committee_size,
+        false,
+        None,
+        Duration::ZERO,
+    )
+}
+
+/// A small message sent by the node with index `node_id`
+fn test_message(node_id: u64) -> Message<TestTypes> {
+    Message {
+        sender: BLSPubKey::generated_from_seed_indexed([0u8; 32], node_id).0,
+        kind: MessageKind::Data(DataMessage::SubmitTransaction(
+            TestTransaction(vec![0, 1, 2, 3]),
+            ViewNumber::new(0),
+        )),
+    }
+}
 
 /// Push CDN network test
 #[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
@@ -43,3 +103,1538 @@ async fn push_cdn_network() {
         .await;
     shutdown_logging();
 }
+
+/// A dead receive task should surface a terminal error on every receive, rather than
+/// the transient error callers retry on forever.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_closed_receive_channel() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+
+    // Kill the task feeding the receive channel
+    network.kill_receive_task().await;
+
+    for _ in 0..3 {
+        assert!(matches!(
+            network.recv_msgs().await,
+            Err(NetworkError::ReceiveChannelClosed)
+        ));
+    }
+    shutdown_logging();
+}
+
+/// After shutting down, the network should refuse to send or receive and report itself
+/// as not ready.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_shut_down() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+    assert!(network.is_ready().await);
+
+    network.shut_down().await;
+
+    assert!(!network.is_ready().await);
+    assert!(matches!(
+        ConnectedNetwork::broadcast_message(
+            network.as_ref(),
+            test_message(0),
+            BTreeSet::new(),
+            STATIC_VER_0_1
+        )
+        .await,
+        Err(NetworkError::ShutDown)
+    ));
+    assert!(matches!(
+        network
+            .direct_message(
+                test_message(0),
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+                STATIC_VER_0_1
+            )
+            .await,
+        Err(NetworkError::ShutDown)
+    ));
+    assert!(matches!(
+        network.recv_msgs().await,
+        Err(NetworkError::ShutDown)
+    ));
+    shutdown_logging();
+}
+
+/// With a receive timeout, `recv_msgs` on a quiet network returns an empty batch instead of
+/// waiting forever, and still delivers messages that do arrive
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_receive_timeout() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let receiver = receiver
+        .as_ref()
+        .clone()
+        .with_receive_timeout(Duration::from_millis(200));
+
+    // Nobody is sending, so we should hear nothing, quickly
+    let received = async_timeout(Duration::from_secs(2), receiver.recv_msgs())
+        .await
+        .expect("receive timeout didn't fire")
+        .expect("failed to receive");
+    assert!(received.is_empty());
+
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), async {
+        loop {
+            let received = receiver.recv_msgs().await.expect("failed to receive");
+            if !received.is_empty() {
+                break received;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for direct message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// A flood of direct messages is cut off at the direct rate limit, while broadcasts still get
+/// through
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_inbound_rate_limit() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(3);
+    let (abuser, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let (neighbour, _) = generator(2).await;
+    let receiver_key = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+    let receiver = receiver
+        .as_ref()
+        .clone()
+        .with_receive_timeout(Duration::from_millis(500))
+        .with_inbound_rate_limit(InboundRateLimit {
+            direct: RateLimit {
+                burst: 5,
+                per_second: 0.1,
+            },
+            ..InboundRateLimit::default()
+        });
+
+    for _ in 0..50 {
+        abuser
+            .direct_message(test_message(0), receiver_key, STATIC_VER_0_1)
+            .await
+            .expect("failed to send direct message");
+    }
+    neighbour
+        .broadcast_to_topic(test_message(2), Topic::Global, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+
+    // Receive until things go quiet
+    let received = async_timeout(Duration::from_secs(10), async {
+        let mut received = Vec::new();
+        loop {
+            let batch = receiver.recv_msgs().await.expect("failed to receive");
+            if batch.is_empty() && received.contains(&test_message(2)) {
+                break received;
+            }
+            received.extend(batch);
+        }
+    })
+    .await
+    .expect("timed out waiting for the broadcast");
+    let from_abuser = received
+        .iter()
+        .filter(|message| **message == test_message(0))
+        .count();
+    assert!(
+        from_abuser <= 6,
+        "accepted {from_abuser} direct messages over the rate limit"
+    );
+    assert_eq!(
+        received
+            .iter()
+            .filter(|message| **message == test_message(2))
+            .count(),
+        1
+    );
+    shutdown_logging();
+}
+
+/// Messages larger than the maximum message size are refused before they are sent
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_max_message_size() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+    let network = network.as_ref().clone().with_max_message_size(16);
+
+    let recipient = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+    match network
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+    {
+        Err(NetworkError::MessageTooLarge { size, limit }) => {
+            assert!(size > 16);
+            assert_eq!(limit, 16);
+        }
+        other => panic!("expected a message too large error, got {other:?}"),
+    }
+    assert!(matches!(
+        ConnectedNetwork::broadcast_message(
+            &network,
+            test_message(0),
+            BTreeSet::new(),
+            STATIC_VER_0_1
+        )
+        .await,
+        Err(NetworkError::MessageTooLarge { .. })
+    ));
+    shutdown_logging();
+}
+
+/// A ping round-trips through the CDN without surfacing in `recv_msgs`, and fails once we
+/// have shut down.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_ping() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+
+    let latency = network.ping().await.expect("failed to ping");
+    assert!(latency < Duration::from_secs(5));
+    assert!(network.is_ready().await);
+
+    // Already connected, so this shouldn't wait
+    async_timeout(Duration::from_millis(100), network.wait_for_ready())
+        .await
+        .expect("wait_for_ready waited while connected");
+
+    // The ping was answered by the network, not handed to us
+    assert!(
+        async_timeout(Duration::from_secs(1), network.recv_msgs())
+            .await
+            .is_err(),
+        "received our own ping"
+    );
+
+    // Shutting down drops the connection, which listeners hear about
+    let mut events = network.connection_events();
+    network.shut_down().await;
+    assert_eq!(
+        events.try_next().expect("no connection event"),
+        Some(ConnectionEvent::Disconnected)
+    );
+    assert!(matches!(network.ping().await, Err(NetworkError::ShutDown)));
+    assert!(!network.is_ready().await);
+    shutdown_logging();
+}
+
+/// Keepalive pings keep us marked connected without ever reaching `recv_msgs`, and stop when we
+/// shut down
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_keepalive() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+    let network = network
+        .as_ref()
+        .clone()
+        .with_receive_timeout(Duration::from_secs(1))
+        .with_connection_tuning(&ConnectionTuning {
+            keepalive_interval: Some(Duration::from_millis(50)),
+            ..ConnectionTuning::default()
+        });
+    let mut events = network.connection_events();
+
+    // Plenty of keepalives go by, none of which are handed to us
+    assert!(network.recv_msgs().await.unwrap().is_empty());
+    assert!(network.is_ready().await);
+    assert!(
+        events.try_next().is_err(),
+        "keepalives changed our connection state"
+    );
+
+    network.shut_down().await;
+    assert_eq!(
+        events.try_next().expect("no connection event"),
+        Some(ConnectionEvent::Disconnected)
+    );
+    shutdown_logging();
+}
+
+/// Sends should count as in-flight while the broker is stalled, and stop counting once
+/// they resolve.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_in_flight_message_count() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    const NUM_MESSAGES: usize = 10;
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+    assert_eq!(network.in_flight_message_count(), Some(0));
+
+    // Stall the broker and dispatch our sends
+    let stall = network.stall_sends().await;
+    let mut sends = Vec::new();
+    for _ in 0..NUM_MESSAGES {
+        let network = Arc::clone(&network);
+        sends.push(async_spawn(async move {
+            ConnectedNetwork::broadcast_message(
+                network.as_ref(),
+                test_message(0),
+                BTreeSet::new(),
+                STATIC_VER_0_1,
+            )
+            .await
+        }));
+    }
+
+    // Wait for every send to be dispatched
+    async_timeout(Duration::from_secs(5), async {
+        while network.in_flight_message_count() != Some(NUM_MESSAGES) {
+            async_sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("sends were never dispatched");
+
+    // Un-stall the broker and let the sends resolve
+    drop(stall);
+    for send in sends {
+        #[cfg(async_executor_impl = "tokio")]
+        let send = send.await.expect("send task panicked");
+        #[cfg(async_executor_impl = "async-std")]
+        let send = send.await;
+        send.expect("failed to send message");
+    }
+
+    assert_eq!(network.in_flight_message_count(), Some(0));
+    shutdown_logging();
+}
+
+/// Flushing waits for in-flight sends to resolve, up to its timeout, and shutting down
+/// flushes before closing the client
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_flush() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    const NUM_MESSAGES: usize = 10;
+
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(0).await;
+
+    // Nothing in flight, so there is nothing to wait for
+    network
+        .flush(Duration::from_millis(100))
+        .await
+        .expect("flushing with nothing in flight failed");
+
+    // Stall the broker and dispatch our sends
+    let spawn_sends = |network: &Arc<PushCdnNetwork<TestTypes>>| {
+        (0..NUM_MESSAGES)
+            .map(|_| {
+                let network = Arc::clone(network);
+                async_spawn(async move {
+                    ConnectedNetwork::broadcast_message(
+                        network.as_ref(),
+                        test_message(0),
+                        BTreeSet::new(),
+                        STATIC_VER_0_1,
+                    )
+                    .await
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    let wait_for_dispatch = |network: Arc<PushCdnNetwork<TestTypes>>| async move {
+        async_timeout(Duration::from_secs(5), async {
+            while network.in_flight_message_count() != Some(NUM_MESSAGES) {
+                async_sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("sends were never dispatched");
+    };
+
+    let stall = network.stall_sends().await;
+    let sends = spawn_sends(&network);
+    wait_for_dispatch(Arc::clone(&network)).await;
+
+    // A stalled send can't resolve, so flushing gives up
+    assert!(matches!(
+        network.flush(Duration::from_millis(100)).await,
+        Err(NetworkError::Timeout { .. })
+    ));
+
+    // Once the broker un-stalls, flushing waits for every send to complete
+    drop(stall);
+    network
+        .flush(Duration::from_secs(5))
+        .await
+        .expect("sends did not complete");
+    assert_eq!(network.in_flight_message_count(), Some(0));
+    for send in sends {
+        #[cfg(async_executor_impl = "tokio")]
+        let send = send.await.expect("send task panicked");
+        #[cfg(async_executor_impl = "async-std")]
+        let send = send.await;
+        send.expect("failed to send message");
+    }
+
+    // Shutting down lets sends already in flight complete first
+    let stall = network.stall_sends().await;
+    let sends = spawn_sends(&network);
+    wait_for_dispatch(Arc::clone(&network)).await;
+    let shut_down = {
+        let network = Arc::clone(&network);
+        async_spawn(async move { network.shut_down().await })
+    };
+    async_sleep(Duration::from_millis(200)).await;
+    drop(stall);
+    #[cfg(async_executor_impl = "tokio")]
+    shut_down.await.expect("shut down task panicked");
+    #[cfg(async_executor_impl = "async-std")]
+    shut_down.await;
+    assert_eq!(network.in_flight_message_count(), Some(0));
+    for send in sends {
+        #[cfg(async_executor_impl = "tokio")]
+        let send = send.await.expect("send task panicked");
+        #[cfg(async_executor_impl = "async-std")]
+        let send = send.await;
+        send.expect("send was abandoned by shutting down");
+    }
+    shutdown_logging();
+}
+
+/// Sends that go through a send queue are still delivered, and the queue stops with us
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_send_queue() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let sender = sender
+        .as_ref()
+        .clone()
+        .with_send_queue(SendQueueConfig::default());
+    let recipient = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+
+    // Two bulk messages, one broadcast and one direct
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    sender
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+        .expect("failed to send direct message");
+    for _ in 0..2 {
+        let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+            .await
+            .expect("timed out waiting for message")
+            .expect("failed to receive message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+
+    sender.shut_down().await;
+    assert!(matches!(
+        sender
+            .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+            .await,
+        Err(NetworkError::ShutDown)
+    ));
+    shutdown_logging();
+}
+
+/// Subscribing should be idempotent, and we should never be able to leave the global topic
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_subscriptions() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 1 is not on the DA committee, so starts off only subscribed to the global topic
+    let generator = push_cdn_generator(1);
+    let (network, _) = generator(1).await;
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::Global])
+    );
+
+    // Join the DA committee (twice)
+    network
+        .subscribe(vec![Topic::DA])
+        .await
+        .expect("failed to subscribe");
+    network
+        .subscribe(vec![Topic::DA, Topic::Global])
+        .await
+        .expect("failed to subscribe");
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::DA, Topic::Global])
+    );
+    assert_eq!(
+        network.subscribed_topics().await,
+        BTreeSet::from([Topic::DA, Topic::Global])
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+
+    // We can't leave the global topic
+    assert!(matches!(
+        network.unsubscribe(vec![Topic::DA, Topic::Global]).await,
+        Err(NetworkError::PushCdnNetwork {
+            source: PushCdnNetworkError::CannotUnsubscribeFromGlobal
+        })
+    ));
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::DA, Topic::Global])
+    );
+
+    // Leave the DA committee
+    network
+        .unsubscribe(vec![Topic::DA])
+        .await
+        .expect("failed to unsubscribe");
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::Global])
+    );
+    shutdown_logging();
+}
+
+/// After losing our connection, we should be subscribed to the same topics on the new one,
+/// including those we subscribed to after connecting
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_resubscribe_after_reconnect() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 1 is not on the DA committee, so has to join it itself
+    let generator = push_cdn_generator(1);
+    let (sender, _) = generator(0).await;
+    let (network, _) = generator(1).await;
+    network
+        .subscribe(vec![Topic::DA])
+        .await
+        .expect("failed to subscribe");
+
+    network
+        .drop_connection()
+        .await
+        .expect("failed to reconnect");
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::DA, Topic::Global])
+    );
+
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+        .await
+        .expect("timed out waiting for broadcast message")
+        .expect("failed to receive broadcast message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// A subscription made while we reconnect should land on the new connection, not be lost
+/// with the old one
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_subscribe_during_reconnect() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 1 is not on the DA committee, so has to join it itself
+    let generator = push_cdn_generator(1);
+    let (sender, _) = generator(0).await;
+    let (network, _) = generator(1).await;
+
+    let (subscribed, reconnected) = futures::join!(
+        network.subscribe(vec![Topic::DA]),
+        network.drop_connection()
+    );
+    subscribed.expect("failed to subscribe");
+    reconnected.expect("failed to reconnect");
+    assert_eq!(
+        network.subscriptions().await,
+        BTreeSet::from([Topic::DA, Topic::Global])
+    );
+
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+        .await
+        .expect("timed out waiting for broadcast message")
+        .expect("failed to receive broadcast message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// Outgoing messages should be subject to the generator's reliability config
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_reliability_config() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // A network that drops every message
+    let generator =
+        <PushCdnNetwork<TestTypes> as TestableNetworkingImplementation<TestTypes>>::generator(
+            2,
+            0,
+            0,
+            2,
+            false,
+            Some(Box::new(AsynchronousNetwork::new(0, 1, 0, 0))),
+            Duration::ZERO,
+        );
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send message");
+
+    // The message should never arrive
+    assert!(
+        async_timeout(Duration::from_secs(2), receiver.recv_msgs())
+            .await
+            .is_err(),
+        "received a message that should have been dropped"
+    );
+    assert_eq!(sender.in_flight_message_count(), Some(0));
+    shutdown_logging();
+}
+
+/// Messages should be deserialized with the version they were serialized with, and we
+/// should be able to tell future versions apart from garbage
+#[test]
+fn push_cdn_deserialize_versioned() {
+    // A valid 0.1 message
+    let message = test_message(0);
+    let serialized = Serializer::<Version01>::serialize(&message).unwrap();
+    let deserialized: Message<TestTypes> = deserialize_versioned(&serialized).unwrap();
+    assert_eq!(deserialized, message);
+
+    // A message from the future
+    let serialized = Serializer::<StaticVersion<37, 17>>::serialize(&message).unwrap();
+    match deserialize_versioned::<TestTypes>(&serialized) {
+        Err(NetworkError::FailedToDeserialize { source }) => {
+            assert!(source.to_string().contains("unsupported future version"));
+        }
+        other => panic!("expected a future version error, got {other:?}"),
+    }
+
+    // Garbage
+    match deserialize_versioned::<TestTypes>(&[0x01]) {
+        Err(NetworkError::FailedToDeserialize { source }) => {
+            assert!(source.to_string().contains("unparseable version"));
+        }
+        other => panic!("expected an unparseable version error, got {other:?}"),
+    }
+}
+
+/// Messages should be delivered when the CDN is made up of more than the default number of
+/// brokers
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_four_brokers() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = PushCdnNetwork::<TestTypes>::generator_with_config(
+        &PushCdnTestConfig {
+            num_brokers: 4,
+            ..PushCdnTestConfig::default()
+        },
+        2,
+        None,
+    );
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+
+    // Direct
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![test_message(0)]);
+
+    // Broadcast
+    ConnectedNetwork::broadcast_message(
+        sender.as_ref(),
+        test_message(0),
+        BTreeSet::new(),
+        STATIC_VER_0_1,
+    )
+    .await
+    .expect("failed to broadcast message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for broadcast message")
+        .expect("failed to receive broadcast message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// Broadcasts go to the whole topic, unless we opt in to direct fan-out, after which
+/// broadcasts to a small recipient set only go to those recipients
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_broadcast_recipients() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(3);
+    let (network, _) = generator(0).await;
+    let sender = network.as_ref().clone().with_direct_fanout_threshold(4);
+    let (recipient, _) = generator(1).await;
+    let (bystander, _) = generator(2).await;
+
+    // Everyone
+    ConnectedNetwork::broadcast_message(&sender, test_message(0), BTreeSet::new(), STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    for network in [&recipient, &bystander] {
+        let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+            .await
+            .expect("timed out waiting for broadcast message")
+            .expect("failed to receive broadcast message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+
+    // Without opting in, a small recipient set still gets the whole topic
+    ConnectedNetwork::broadcast_message(
+        network.as_ref(),
+        test_message(0),
+        BTreeSet::from([BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0]),
+        STATIC_VER_0_1,
+    )
+    .await
+    .expect("failed to broadcast message");
+    for network in [&recipient, &bystander] {
+        let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+            .await
+            .expect("timed out waiting for broadcast message")
+            .expect("failed to receive broadcast message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+
+    // Just the recipient
+    ConnectedNetwork::broadcast_message(
+        &sender,
+        test_message(0),
+        BTreeSet::from([BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0]),
+        STATIC_VER_0_1,
+    )
+    .await
+    .expect("failed to fan out message");
+    let received = async_timeout(Duration::from_secs(5), recipient.recv_msgs())
+        .await
+        .expect("timed out waiting for fanned out message")
+        .expect("failed to receive fanned out message");
+    assert_eq!(received, vec![test_message(0)]);
+    assert!(
+        async_timeout(Duration::from_secs(2), bystander.recv_msgs())
+            .await
+            .is_err(),
+        "bystander received a message that was not meant for it"
+    );
+    shutdown_logging();
+}
+
+/// Sends from a generator given a secondary network delay are held back by that delay
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_secondary_network_delay() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let delay = Duration::from_millis(500);
+    let generator =
+        <PushCdnNetwork<TestTypes> as TestableNetworkingImplementation<TestTypes>>::generator(
+            2, 0, 0, 2, false, None, delay,
+        );
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+
+    let start = Instant::now();
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![test_message(0)]);
+    assert!(start.elapsed() >= delay, "message was not delayed");
+    shutdown_logging();
+}
+
+/// Sends while paused are silently dropped, and sending works again once resumed
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_pause_resume() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let recipient = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+
+    // Paused sends succeed, but nothing arrives
+    sender.pause();
+    sender
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+        .expect("paused send should be a no-op");
+    assert!(
+        async_timeout(Duration::from_secs(2), receiver.recv_msgs())
+            .await
+            .is_err(),
+        "received a message sent while paused"
+    );
+
+    // Resumed sends arrive
+    sender.resume();
+    sender
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// Each send is serialized with the version chosen for that call, so the same network can
+/// send messages with different versions side by side. A receiver that doesn't support a
+/// newer version yet skips those messages and carries on.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_per_message_version() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let recipient = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+
+    // A message sent with 0.1 is read back as 0.1
+    sender
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![test_message(0)]);
+
+    // Messages sent with 0.2 carry the 0.2 prefix, which the receiver doesn't support yet.
+    // They are skipped, rather than failing the receive, and later messages still arrive.
+    for _ in 0..3 {
+        sender
+            .direct_message(test_message(1), recipient, StaticVersion::<0, 2> {})
+            .await
+            .expect("failed to send direct message");
+    }
+    sender
+        .direct_message(test_message(0), recipient, STATIC_VER_0_1)
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), async {
+        loop {
+            let received = receiver
+                .recv_msgs()
+                .await
+                .expect("failed to receive direct message");
+            if !received.is_empty() {
+                break received;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for direct message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// A broadcast to several topics at once reaches the members of each of them
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_broadcast_multi() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Nodes 0 and 1 are on the DA committee, node 2 is not
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (da_member, _) = generator(1).await;
+    let (quorum_member, _) = generator(2).await;
+
+    sender
+        .broadcast_message_multi(
+            test_message(0),
+            vec![Topic::DA, Topic::Global],
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to broadcast message");
+    for network in [&da_member, &quorum_member] {
+        let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+            .await
+            .expect("timed out waiting for broadcast message")
+            .expect("failed to receive broadcast message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+    shutdown_logging();
+}
+
+/// A broadcast to a single topic only reaches the nodes subscribed to it
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_broadcast_to_topic() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Nodes 0 and 1 are on the DA committee, node 2 is not
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (da_member, _) = generator(1).await;
+    let (quorum_member, _) = generator(2).await;
+
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    let received = async_timeout(Duration::from_secs(5), da_member.recv_msgs())
+        .await
+        .expect("timed out waiting for broadcast message")
+        .expect("failed to receive broadcast message");
+    assert_eq!(received, vec![test_message(0)]);
+    assert!(
+        async_timeout(Duration::from_secs(2), quorum_member.recv_msgs())
+            .await
+            .is_err(),
+        "received a broadcast to a topic we aren't subscribed to"
+    );
+    shutdown_logging();
+}
+
+/// Received messages say whether they were broadcast, and to which topics, or sent directly
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_message_origin() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let receiver_key = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs_with_meta())
+        .await
+        .expect("timed out waiting for broadcast message")
+        .expect("failed to receive broadcast message");
+    assert_eq!(
+        received,
+        vec![(test_message(0), MessageOrigin::Broadcast(vec![Topic::DA]))]
+    );
+
+    sender
+        .direct_message(test_message(0), receiver_key, STATIC_VER_0_1)
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs_with_meta())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![(test_message(0), MessageOrigin::Direct)]);
+    shutdown_logging();
+}
+
+/// Successful sends are counted in bytes, per topic, directly, and in total
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_bytes_sent() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let size = Serializer::<Version01>::serialize(&test_message(0))
+        .expect("failed to serialize message")
+        .len() as u64;
+    assert_eq!(sender.total_bytes_sent(), 0);
+
+    sender
+        .broadcast_message_multi(
+            test_message(0),
+            vec![Topic::Global, Topic::DA],
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to broadcast message");
+    assert_eq!(sender.bytes_sent(Topic::Global), size);
+    assert_eq!(sender.bytes_sent(Topic::DA), size);
+    assert_eq!(sender.total_bytes_sent(), size);
+
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    assert_eq!(sender.direct_bytes_sent(), size);
+    assert_eq!(sender.bytes_sent(Topic::DA), size);
+    assert_eq!(sender.total_bytes_sent(), 2 * size);
+
+    // Receiving isn't sending
+    async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for messages")
+        .expect("failed to receive messages");
+    assert_eq!(receiver.total_bytes_sent(), 0);
+    shutdown_logging();
+}
+
+/// Tests can put any nodes on the DA committee, not just the first few
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_custom_topic_assignment() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // By default the first `da_committee_size` nodes are on the DA committee
+    let default_assignment = TopicAssignment::first_nodes_in_da(2);
+    assert_eq!(default_assignment.topics(1), vec![Topic::DA, Topic::Global]);
+    assert_eq!(default_assignment.topics(2), vec![Topic::Global]);
+
+    // Odd-indexed nodes are on the DA committee
+    let generator = PushCdnNetwork::<TestTypes>::generator_with_config(
+        &PushCdnTestConfig {
+            topics: Some(TopicAssignment::new(|node_id| {
+                if node_id % 2 == 1 {
+                    vec![Topic::DA, Topic::Global]
+                } else {
+                    vec![Topic::Global]
+                }
+            })),
+            ..PushCdnTestConfig::default()
+        },
+        2,
+        None,
+    );
+    let (sender, _) = generator(0).await;
+    let (da_member, _) = generator(1).await;
+    let (quorum_member, _) = generator(2).await;
+    let (other_da_member, _) = generator(3).await;
+
+    sender
+        .broadcast_to_topic(test_message(0), Topic::DA, STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    for network in [&da_member, &other_da_member] {
+        let received = async_timeout(Duration::from_secs(5), network.recv_msgs())
+            .await
+            .expect("timed out waiting for broadcast message")
+            .expect("failed to receive broadcast message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+    assert!(
+        async_timeout(Duration::from_secs(2), quorum_member.recv_msgs())
+            .await
+            .is_err(),
+        "node 2 received a broadcast to the DA committee it isn't on"
+    );
+    shutdown_logging();
+}
+
+/// With a fixed seed, messages sent concurrently by several nodes are received in the same
+/// order every run
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_deterministic_receive_ordering() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    const NUM_SENDERS: u64 = 3;
+    const MESSAGES_PER_SENDER: u64 = 4;
+
+    // The `index`th message from `node_id`
+    let message = |node_id, index| Message::<TestTypes> {
+        sender: BLSPubKey::generated_from_seed_indexed([0u8; 32], node_id).0,
+        kind: MessageKind::Data(DataMessage::SubmitTransaction(
+            TestTransaction(vec![0, 1, 2, 3]),
+            ViewNumber::new(index),
+        )),
+    };
+
+    // Every sender sends its messages at once, to a fresh CDN each run
+    let run = || async move {
+        let generator = PushCdnNetwork::<TestTypes>::generator_with_config(
+            &PushCdnTestConfig {
+                receive_ordering: Some(ReceiveOrdering {
+                    seed: 42,
+                    batch_size: (NUM_SENDERS * MESSAGES_PER_SENDER) as usize,
+                }),
+                ..PushCdnTestConfig::default()
+            },
+            1,
+            None,
+        );
+        let (receiver, _) = generator(0).await;
+        let mut senders = Vec::new();
+        for node_id in 1..=NUM_SENDERS {
+            senders.push(generator(node_id).await.0);
+        }
+
+        let recipient = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+        let sends = senders.iter().zip(1..).flat_map(|(sender, node_id)| {
+            (0..MESSAGES_PER_SENDER).map(move |index| {
+                sender.direct_message(message(node_id, index), recipient, STATIC_VER_0_1)
+            })
+        });
+        for result in futures::future::join_all(sends).await {
+            result.expect("failed to send direct message");
+        }
+
+        let mut received = Vec::new();
+        while received.len() < (NUM_SENDERS * MESSAGES_PER_SENDER) as usize {
+            received.extend(
+                async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+                    .await
+                    .expect("timed out waiting for messages")
+                    .expect("failed to receive messages"),
+            );
+        }
+        received
+    };
+
+    let first = run().await;
+    let second = run().await;
+    assert_eq!(first, second);
+
+    // Every message was received exactly once
+    for node_id in 1..=NUM_SENDERS {
+        for index in 0..MESSAGES_PER_SENDER {
+            assert_eq!(
+                first
+                    .iter()
+                    .filter(|received| **received == message(node_id, index))
+                    .count(),
+                1
+            );
+        }
+    }
+    shutdown_logging();
+}
+
+/// The message stream yields each received message in turn, and ends when we shut down
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_message_stream() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    const NUM_MESSAGES: u64 = 5;
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+    let mut messages = Box::pin(receiver.as_ref().clone().into_message_stream());
+
+    let sent: Vec<_> = (0..NUM_MESSAGES).map(test_message).collect();
+    for message in &sent {
+        sender
+            .direct_message(
+                message.clone(),
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+                STATIC_VER_0_1,
+            )
+            .await
+            .expect("failed to send direct message");
+    }
+
+    let received: Vec<_> = async_timeout(
+        Duration::from_secs(5),
+        messages.by_ref().take(sent.len()).collect::<Vec<_>>(),
+    )
+    .await
+    .expect("timed out waiting for messages")
+    .into_iter()
+    .collect::<Result<_, _>>()
+    .expect("failed to receive messages");
+    assert_eq!(received, sent);
+
+    receiver.shut_down().await;
+    assert!(async_timeout(Duration::from_secs(5), messages.next())
+        .await
+        .expect("the stream didn't end after shutting down")
+        .is_none());
+    shutdown_logging();
+}
+
+/// Creating a client with a topic we don't know fails before connecting, naming the topic
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_rejects_unknown_topic() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Nothing is listening here, so only the topics can fail
+    let Err(err) = PushCdnNetwork::<TestTypes>::new_from_seed(
+        "127.0.0.1:1".to_string(),
+        vec![Topic::Global.to_string(), "bogus".to_string()],
+        [0u8; 32],
+        0,
+        None,
+    )
+    .await
+    else {
+        panic!("created a client with an unknown topic");
+    };
+    assert!(matches!(
+        err.downcast_ref::<PushCdnNetworkError>(),
+        Some(PushCdnNetworkError::UnknownTopic { topic, .. }) if topic == "bogus"
+    ));
+    shutdown_logging();
+}
+
+/// Messages should be delivered when every broker authenticates with its own keypair
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_distinct_broker_keys() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let test_config = PushCdnTestConfig {
+        distinct_broker_keys: true,
+        ..PushCdnTestConfig::default()
+    };
+    assert_ne!(
+        test_config.broker_keypair::<BLSPubKey>(0).0,
+        test_config.broker_keypair::<BLSPubKey>(1).0
+    );
+
+    let generator = PushCdnNetwork::<TestTypes>::generator_with_config(&test_config, 2, None);
+    let (sender, _) = generator(0).await;
+    let (receiver, _) = generator(1).await;
+
+    sender
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for direct message")
+        .expect("failed to receive direct message");
+    assert_eq!(received, vec![test_message(0)]);
+    shutdown_logging();
+}
+
+/// A direct message sent to several recipients at once reaches each of them
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_direct_message_multi() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(3);
+    let (sender, _) = generator(0).await;
+    let (first_recipient, _) = generator(1).await;
+    let (second_recipient, _) = generator(2).await;
+
+    let failures = sender
+        .direct_message_multi(
+            test_message(0),
+            vec![
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], 2).0,
+            ],
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct messages");
+    assert!(failures.is_empty(), "failed to reach {failures:?}");
+
+    for recipient in [&first_recipient, &second_recipient] {
+        let received = async_timeout(Duration::from_secs(5), recipient.recv_msgs())
+            .await
+            .expect("timed out waiting for direct message")
+            .expect("failed to receive direct message");
+        assert_eq!(received, vec![test_message(0)]);
+    }
+    shutdown_logging();
+}
+
+/// Node lookups queued with the Push CDN are kept (up to a limit) for a combined network to
+/// forward
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_queued_lookups() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let generator = push_cdn_generator(2);
+    let (network, _) = generator(0).await;
+    assert!(network.drain_queued_lookups().is_empty());
+
+    let lookup = |view| {
+        (
+            ViewNumber::new(view),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], view).0,
+        )
+    };
+    for view in 0..=MAX_QUEUED_LOOKUPS as u64 {
+        let (view_number, key) = lookup(view);
+        network
+            .queue_node_lookup(view_number, key)
+            .await
+            .expect("failed to queue lookup");
+    }
+
+    // The oldest lookup was dropped to make room
+    let queued = network.drain_queued_lookups();
+    assert_eq!(queued.len(), MAX_QUEUED_LOOKUPS);
+    assert_eq!(queued.first(), Some(&lookup(1)));
+    assert_eq!(queued.last(), Some(&lookup(MAX_QUEUED_LOOKUPS as u64)));
+    assert!(network.drain_queued_lookups().is_empty());
+    shutdown_logging();
+}
+
+/// The mock Push CDN routes broadcasts by topic and direct messages by key, without any brokers
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn mock_push_cdn_routing() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Nodes 0 and 1 are on the DA committee, node 2 is not
+    let generator = <MockPushCdnNetwork<TestTypes> as TestableNetworkingImplementation<
+        TestTypes,
+    >>::generator(3, 0, 0, 2, false, None, Duration::ZERO);
+    let (da_member, _) = generator(0).await;
+    let (other_da_member, _) = generator(1).await;
+    let (quorum_member, _) = generator(2).await;
+
+    // DA broadcasts only reach the DA committee
+    da_member
+        .da_broadcast_message(test_message(0), BTreeSet::new(), STATIC_VER_0_1)
+        .await
+        .expect("failed to broadcast message");
+    for network in [&da_member, &other_da_member] {
+        assert_eq!(network.recv_msgs().await.unwrap(), vec![test_message(0)]);
+    }
+    assert!(
+        async_timeout(Duration::from_millis(100), quorum_member.recv_msgs())
+            .await
+            .is_err(),
+        "DA broadcast reached a node that is not on the DA committee"
+    );
+
+    // Global broadcasts reach everyone
+    ConnectedNetwork::broadcast_message(
+        quorum_member.as_ref(),
+        test_message(2),
+        BTreeSet::new(),
+        STATIC_VER_0_1,
+    )
+    .await
+    .expect("failed to broadcast message");
+    for network in [&da_member, &other_da_member, &quorum_member] {
+        assert_eq!(network.recv_msgs().await.unwrap(), vec![test_message(2)]);
+    }
+
+    // Direct messages only reach the recipient
+    da_member
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 2).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("failed to send direct message");
+    assert_eq!(
+        quorum_member.recv_msgs().await.unwrap(),
+        vec![test_message(0)]
+    );
+    assert!(
+        async_timeout(Duration::from_millis(100), other_da_member.recv_msgs())
+            .await
+            .is_err(),
+        "direct message reached a node it was not addressed to"
+    );
+
+    // Paused sends are dropped
+    da_member.pause();
+    da_member
+        .direct_message(
+            test_message(0),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], 2).0,
+            STATIC_VER_0_1,
+        )
+        .await
+        .expect("paused send should be a no-op");
+    assert!(
+        async_timeout(Duration::from_millis(100), quorum_member.recv_msgs())
+            .await
+            .is_err(),
+        "received a message sent while paused"
+    );
+    shutdown_logging();
+}
+
+/// With batching enabled, messages that are already waiting are received together
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn push_cdn_recv_batch() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    const NUM_MESSAGES: usize = 3;
+
+    let generator = push_cdn_generator(2);
+    let (sender, _) = generator(0).await;
+    let (receiver, other_handle) = generator(1).await;
+    drop(other_handle);
+    let receiver = Arc::try_unwrap(receiver)
+        .ok()
+        .expect("receiver handle is still shared")
+        .with_max_batch(NUM_MESSAGES + 1);
+
+    for _ in 0..NUM_MESSAGES {
+        sender
+            .direct_message(
+                test_message(0),
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0,
+                STATIC_VER_0_1,
+            )
+            .await
+            .expect("failed to send direct message");
+    }
+
+    // Give every message time to arrive, so they are all ready at once
+    async_sleep(Duration::from_secs(1)).await;
+
+    let received = async_timeout(Duration::from_secs(5), receiver.recv_msgs())
+        .await
+        .expect("timed out waiting for messages")
+        .expect("failed to receive messages");
+    assert_eq!(received, vec![test_message(0); NUM_MESSAGES]);
+    shutdown_logging();
+}
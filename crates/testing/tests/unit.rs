@@ -1,3 +1,4 @@
 mod unit {
+    mod certificate;
     mod message;
 }
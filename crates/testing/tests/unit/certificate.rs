@@ -0,0 +1,92 @@
+use bitvec::bitvec;
+use commit::Committable;
+use ethereum_types::U256;
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    constants::Version01,
+    data::ViewNumber,
+    qc::{
+        assembled_qc_format_version, decode_assembled_qc, encode_assembled_qc, QCDecodingError,
+        ASSEMBLED_QC_ENCODING_VERSION,
+    },
+    signature_key::BLSPubKey,
+    simple_certificate::ViewSyncCommitCertificate2,
+    simple_vote::ViewSyncCommitData,
+    traits::{node_implementation::ConsensusTime, signature_key::SignatureKey},
+    vote::Certificate,
+};
+use versioned_binary_serialization::{BinarySerializer, Serializer};
+
+/// A BLS aggregate signature
+type Signature = <BLSPubKey as SignatureKey>::PureAssembledSignatureType;
+
+/// A certificate signed by a single node with all of the stake
+fn signed_certificate() -> ViewSyncCommitCertificate2<TestTypes> {
+    let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+    let data: ViewSyncCommitData<TestTypes> = ViewSyncCommitData {
+        relay: 0,
+        round: ViewNumber::new(1),
+    };
+    let commitment = data.commit();
+
+    let signature = BLSPubKey::sign(&private_key, commitment.as_ref()).unwrap();
+    let qc_pp =
+        BLSPubKey::get_public_parameter(vec![public_key.get_stake_table_entry(1)], U256::from(1));
+    let signatures = BLSPubKey::assemble(&qc_pp, bitvec![1; 1].as_bitslice(), &[signature]);
+
+    ViewSyncCommitCertificate2::create_signed_certificate(
+        commitment,
+        data,
+        signatures,
+        ViewNumber::new(1),
+    )
+}
+
+/// A certificate's assembled signatures, encoded for the wire
+fn encoded_signatures(certificate: &ViewSyncCommitCertificate2<TestTypes>) -> Vec<u8> {
+    let (signature, signers) = certificate
+        .signatures
+        .as_ref()
+        .expect("certificate has no signatures");
+    encode_assembled_qc(signature, signers).unwrap()
+}
+
+#[test]
+// Checks that a certificate's encoded signatures carry their format version, and that the
+// certificate itself still serializes as before.
+fn signature_format_version_round_trip() {
+    let certificate = signed_certificate();
+    let encoded = encoded_signatures(&certificate);
+    assert_eq!(
+        assembled_qc_format_version(&encoded).unwrap(),
+        ASSEMBLED_QC_ENCODING_VERSION
+    );
+    assert_eq!(
+        Some(decode_assembled_qc::<Signature>(&encoded).unwrap()),
+        certificate.signatures
+    );
+
+    let serialized = Serializer::<Version01>::serialize(&certificate).unwrap();
+    let deserialized: ViewSyncCommitCertificate2<TestTypes> =
+        Serializer::<Version01>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, certificate);
+}
+
+#[test]
+// Checks that signatures in a format we don't know are rejected instead of being mis-parsed.
+fn unknown_signature_format_version_is_rejected() {
+    let mut encoded = encoded_signatures(&signed_certificate());
+    let unknown = ASSEMBLED_QC_ENCODING_VERSION + 1;
+    encoded[0] = unknown;
+
+    let err = assembled_qc_format_version(&encoded).unwrap_err();
+    assert!(matches!(err, QCDecodingError::UnsupportedVersion { version } if version == unknown));
+    assert_eq!(
+        err.to_string(),
+        format!("unsupported assembled QC encoding version {unknown}")
+    );
+    assert!(matches!(
+        decode_assembled_qc::<Signature>(&encoded),
+        Err(QCDecodingError::UnsupportedVersion { .. })
+    ));
+}
@@ -0,0 +1,890 @@
+use std::{num::NonZeroUsize, time::Instant};
+
+use bitvec::bitvec;
+use commit::{Commitment, CommitmentBoundsArkless, Committable};
+use hotshot_example_types::{node_types::TestTypes, state_types::TestInstanceState};
+use hotshot_testing::task_helpers::{build_system_handle, key_pair_for_id};
+use hotshot_types::{
+    data::{Leaf, ViewNumber},
+    simple_certificate::{
+        QcCache, QuorumCertificate, ViewSyncCommitCertificate2, ViewSyncPreCommitCertificate2,
+    },
+    simple_vote::{
+        QuorumData, SimpleVote, ViewSyncCommitData, ViewSyncCommitVote, ViewSyncPreCommitData,
+        ViewSyncPreCommitVote, Voteable,
+    },
+    traits::{
+        election::{commit_to_stake_table, Membership},
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+    },
+    vote::{
+        AssemblyPrecondition, Certificate, Vote, VoteAccumulator, VoteAccumulatorError, VoteError,
+    },
+};
+
+/// An empty accumulator for votes from `membership`
+fn accumulator<VOTE, CERT>(
+    membership: &<TestTypes as NodeType>::Membership,
+) -> VoteAccumulator<TestTypes, VOTE, CERT>
+where
+    VOTE: Vote<TestTypes>,
+    CERT: Certificate<TestTypes, Voteable = VOTE::Commitment>,
+{
+    VoteAccumulator::new(membership).expect("failed to create accumulator")
+}
+
+/// A vote on `data` signed by the node with index `node_id`
+fn signed_vote<DATA: Voteable + 'static>(node_id: u64, data: DATA) -> SimpleVote<TestTypes, DATA> {
+    let (private_key, public_key) = key_pair_for_id(node_id);
+    SimpleVote::create_signed_vote(data, ViewNumber::new(1), &public_key, &private_key)
+        .expect("failed to sign vote")
+}
+
+/// View sync pre-commit certificates form at f + 1, commit certificates at 2f + 1, and votes
+/// for different relays are accumulated separately.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_view_sync_vote_accumulation() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.view_sync_membership.clone();
+    let failure_threshold = membership.failure_threshold().get();
+    let success_threshold = membership.success_threshold().get();
+
+    let precommit = |node_id, relay| -> ViewSyncPreCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncPreCommitData {
+                relay,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let commit = |node_id, relay| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+
+    // Pre-commit: one short of f + 1 for relay 0
+    let mut accumulator = accumulator::<_, ViewSyncPreCommitCertificate2<TestTypes>>(&membership);
+    for node_id in 0..failure_threshold - 1 {
+        assert!(accumulator
+            .accumulate(&precommit(node_id, 0), &membership)
+            .unwrap()
+            .is_left());
+    }
+
+    // A vote for a different relay doesn't count towards relay 0
+    assert!(accumulator
+        .accumulate(&precommit(failure_threshold - 1, 1), &membership)
+        .unwrap()
+        .is_left());
+
+    // The last vote for relay 0 forms the certificate
+    let certificate = accumulator
+        .accumulate(&precommit(failure_threshold, 0), &membership)
+        .unwrap()
+        .right()
+        .expect("expected a pre-commit certificate at f + 1");
+    assert_eq!(certificate.get_data().relay, 0);
+
+    // Commit: f + 1 is not enough, 2f + 1 is
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    for node_id in 0..success_threshold - 1 {
+        assert!(accumulator
+            .accumulate(&commit(node_id, 0), &membership)
+            .unwrap()
+            .is_left());
+    }
+    let certificate = accumulator
+        .accumulate(&commit(success_threshold - 1, 0), &membership)
+        .unwrap()
+        .right()
+        .expect("expected a commit certificate at 2f + 1");
+    assert_eq!(certificate.get_data().relay, 0);
+}
+
+/// The accumulator reports which nodes' votes formed a certificate, in stake table order
+/// whatever order the votes arrived in
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_reports_contributors() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    let mut certificate = None;
+    for node_id in (0..success_threshold).rev() {
+        assert!(accumulator.contributors().is_empty());
+        certificate = accumulator
+            .accumulate(&vote(node_id), &membership)
+            .unwrap()
+            .right();
+    }
+    assert!(certificate.is_some(), "expected a certificate at 2f + 1");
+
+    let expected: Vec<_> = (0..success_threshold)
+        .map(|node_id| key_pair_for_id(node_id).1)
+        .collect();
+    assert_eq!(accumulator.contributors(), expected);
+}
+
+/// A vote with a bad signature is skipped without poisoning the accumulator, so honest votes
+/// still form a certificate.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_skips_invalid_signature() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+
+    // Node 0 claims a signature that was actually made by node 1
+    let mut forged = vote(0);
+    forged.signature.1 = vote(1).signature.1;
+    assert!(accumulator
+        .accumulate(&forged, &membership)
+        .unwrap()
+        .is_left());
+
+    // Node 0's real vote (and everyone else's) still counts
+    for node_id in 0..success_threshold - 1 {
+        assert!(accumulator
+            .accumulate(&vote(node_id), &membership)
+            .unwrap()
+            .is_left());
+    }
+    assert!(accumulator
+        .accumulate(&vote(success_threshold - 1), &membership)
+        .unwrap()
+        .is_right());
+}
+
+/// A duplicated vote is ignored: it doesn't add stake, signers, or signatures.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_ignores_duplicates() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    let vote: ViewSyncCommitVote<TestTypes> = signed_vote(
+        0,
+        ViewSyncCommitData {
+            relay: 0,
+            round: ViewNumber::new(1),
+        },
+    );
+    let commitment = vote.get_data_commitment();
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    assert!(accumulator
+        .accumulate(&vote, &membership)
+        .unwrap()
+        .is_left());
+    let stake = accumulator.vote_outcomes[&commitment].0;
+    let (signers, signatures) = accumulator.signers[&commitment].clone();
+    assert_eq!(signers.count_ones(), 1);
+    assert_eq!(signatures.len(), 1);
+
+    // The same vote again
+    assert!(accumulator
+        .accumulate(&vote, &membership)
+        .unwrap()
+        .is_left());
+    assert_eq!(accumulator.vote_outcomes[&commitment].0, stake);
+    assert_eq!(accumulator.signers[&commitment].0, signers);
+    assert_eq!(accumulator.signers[&commitment].1.len(), 1);
+}
+
+/// A node voting for two different relays in the same view is recorded as an equivocation,
+/// with both signatures as evidence, and both votes are still accumulated.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_detects_equivocation() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    let vote = |node_id, relay| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let (first, second) = (vote(0, 0), vote(0, 1));
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    for vote in [&first, &first, &vote(1, 0), &second, &second] {
+        assert!(accumulator.accumulate(vote, &membership).unwrap().is_left());
+    }
+
+    // Only the conflicting vote is reported, and only once
+    assert_eq!(accumulator.equivocations.len(), 1);
+    let equivocation = &accumulator.equivocations[0];
+    assert_eq!(equivocation.key, first.get_signing_key());
+    assert_eq!(equivocation.view, ViewNumber::new(1));
+    assert_eq!(equivocation.commitment_a, first.get_data_commitment());
+    assert_eq!(equivocation.commitment_b, second.get_data_commitment());
+    assert!(equivocation.key.validate(
+        &equivocation.signature_a,
+        equivocation.commitment_a.as_ref()
+    ));
+    assert!(equivocation.key.validate(
+        &equivocation.signature_b,
+        equivocation.commitment_b.as_ref()
+    ));
+
+    // Accumulation carried on for both commitments
+    assert_eq!(accumulator.signers[&first.get_data_commitment()].1.len(), 2);
+    assert_eq!(
+        accumulator.signers[&second.get_data_commitment()].1.len(),
+        1
+    );
+}
+
+/// Votes that don't count can't make their signer look like an equivocator, and only the
+/// newest view's votes are kept to check against.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_equivocation_bookkeeping() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    let vote = |node_id, relay, view| -> ViewSyncCommitVote<TestTypes> {
+        let (private_key, public_key) = key_pair_for_id(node_id);
+        let data = ViewSyncCommitData {
+            relay,
+            round: ViewNumber::new(view),
+        };
+        SimpleVote::create_signed_vote(data, ViewNumber::new(view), &public_key, &private_key)
+            .expect("failed to sign vote")
+    };
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    assert!(accumulator
+        .accumulate(&vote(0, 0, 1), &membership)
+        .unwrap()
+        .is_left());
+
+    // A conflicting vote with a forged signature is rejected, not reported
+    let mut forged = vote(0, 1, 1);
+    forged.signature.1 = vote(0, 0, 1).get_signature();
+    assert!(accumulator
+        .accumulate(&forged, &membership)
+        .unwrap()
+        .is_left());
+    assert!(accumulator.equivocations.is_empty());
+
+    // A newer view forgets the older one's first votes
+    assert!(accumulator
+        .accumulate(&vote(1, 0, 2), &membership)
+        .unwrap()
+        .is_left());
+    assert_eq!(
+        accumulator.first_votes.keys().collect::<Vec<_>>(),
+        vec![&ViewNumber::new(2)]
+    );
+
+    // And votes for the older view are no longer checked or remembered
+    assert!(accumulator
+        .accumulate(&vote(0, 1, 1), &membership)
+        .unwrap()
+        .is_left());
+    assert!(accumulator.equivocations.is_empty());
+    assert_eq!(accumulator.first_votes[&ViewNumber::new(2)].len(), 1);
+}
+
+/// Progress towards a certificate is visible after each vote, without waiting for it to form.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_progress() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let commitment = vote(0).get_data_commitment();
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    let (accumulated, threshold) = accumulator.accumulated_stake(&commitment, &membership);
+    assert!(accumulated.is_zero());
+    assert_eq!(threshold, success_threshold.into());
+
+    // Every test node has a stake of 1
+    for node_id in 0..success_threshold - 1 {
+        accumulator.accumulate(&vote(node_id), &membership).unwrap();
+        let (accumulated, _) = accumulator.accumulated_stake(&commitment, &membership);
+        assert_eq!(accumulated, (node_id + 1).into());
+    }
+}
+
+/// Once a certificate forms, nothing accumulated for its commitment is kept, while votes for
+/// other commitments are untouched.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_clears_certified_commitment() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    let vote = |node_id, relay| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let (certified, other) = (
+        vote(0, 0).get_data_commitment(),
+        vote(0, 1).get_data_commitment(),
+    );
+
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    assert!(accumulator
+        .accumulate(&vote(success_threshold, 1), &membership)
+        .unwrap()
+        .is_left());
+    for node_id in 0..success_threshold - 1 {
+        assert!(accumulator
+            .accumulate(&vote(node_id, 0), &membership)
+            .unwrap()
+            .is_left());
+    }
+    accumulator
+        .accumulate(&vote(success_threshold - 1, 0), &membership)
+        .unwrap()
+        .right()
+        .expect("expected a commit certificate at 2f + 1");
+
+    assert!(!accumulator.vote_outcomes.contains_key(&certified));
+    assert!(!accumulator.signers.contains_key(&certified));
+    assert!(accumulator
+        .accumulated_stake(&certified, &membership)
+        .0
+        .is_zero());
+    assert_eq!(accumulator.signers[&other].1.len(), 1);
+    assert_eq!(accumulator.vote_outcomes[&other].1.len(), 1);
+}
+
+/// An accumulator seeded with a partial aggregate just below the threshold forms a certificate
+/// from one more vote, and a partial aggregate with a bad signature is rejected.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_from_partial() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+    let num_signers = membership.get_committee_qc_stake_table().len();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let commitment = vote(0).get_data_commitment();
+    type Accumulator = VoteAccumulator<
+        TestTypes,
+        ViewSyncCommitVote<TestTypes>,
+        ViewSyncCommitCertificate2<TestTypes>,
+    >;
+
+    // Every node but the last needed has signed
+    let mut signers = bitvec![0; num_signers];
+    let mut signatures = Vec::new();
+    for node_id in 0..success_threshold - 1 {
+        signers.set(node_id as usize, true);
+        signatures.push(vote(node_id).get_signature());
+    }
+
+    let mut accumulator =
+        Accumulator::from_partial(&membership, commitment, signers.clone(), signatures.clone())
+            .expect("failed to seed accumulator");
+    // Every test node has a stake of 1
+    let (accumulated, _) = accumulator.accumulated_stake(&commitment, &membership);
+    assert_eq!(accumulated, (success_threshold - 1).into());
+
+    // A signer from the partial aggregate can't vote again
+    assert!(matches!(
+        accumulator.try_accumulate(&vote(0), &membership),
+        Err(VoteError::DuplicateVote)
+    ));
+
+    let certificate = accumulator
+        .accumulate(&vote(success_threshold - 1), &membership)
+        .unwrap()
+        .right()
+        .expect("expected a certificate from the last vote");
+    assert!(certificate.is_valid_cert(&membership));
+
+    // Node 1's signature in node 0's place
+    let mut forged = signatures.clone();
+    forged[0] = signatures[1].clone();
+    assert!(matches!(
+        Accumulator::from_partial(&membership, commitment, signers.clone(), forged),
+        Err(VoteAccumulatorError::InvalidPartialSignature { signer: 0 })
+    ));
+    assert!(matches!(
+        Accumulator::from_partial(&membership, commitment, signers, signatures[1..].to_vec()),
+        Err(VoteAccumulatorError::PartialSignatureCount { .. })
+    ));
+}
+
+/// A vote from a node beyond the signers the accumulator was sized for is rejected rather than
+/// indexing past the end of the signers bitvec.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_rejects_unknown_signer_index() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let last_node = membership.total_nodes() as u64 - 1;
+
+    let vote: ViewSyncCommitVote<TestTypes> = signed_vote(
+        last_node,
+        ViewSyncCommitData {
+            relay: 0,
+            round: ViewNumber::new(1),
+        },
+    );
+
+    // Sized for only the first node, so the last node's index is out of range
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    accumulator.num_signers = 1;
+    assert!(accumulator
+        .accumulate(&vote, &membership)
+        .unwrap()
+        .is_left());
+    assert!(accumulator.vote_outcomes[&vote.get_data_commitment()]
+        .1
+        .is_empty());
+}
+
+/// `try_accumulate` says why a vote didn't count, and accumulation carries on afterwards.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_reports_errors() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    let vote = |node_id, relay| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+
+    // Node 1's signature, claimed by node 0
+    let mut forged = vote(0, 0);
+    forged.signature.1 = vote(1, 0).signature.1;
+    assert!(matches!(
+        accumulator.try_accumulate(&forged, &membership),
+        Err(VoteError::InvalidSignature)
+    ));
+
+    let outsider = vote(membership.total_nodes() as u64, 0);
+    assert!(matches!(
+        accumulator.try_accumulate(&outsider, &membership),
+        Err(VoteError::UnknownSigner)
+    ));
+
+    assert!(accumulator
+        .try_accumulate(&vote(0, 0), &membership)
+        .unwrap()
+        .is_left());
+    assert!(matches!(
+        accumulator.try_accumulate(&vote(0, 0), &membership),
+        Err(VoteError::DuplicateVote)
+    ));
+    assert!(matches!(
+        accumulator.try_accumulate(&vote(0, 1), &membership),
+        Err(VoteError::Equivocation)
+    ));
+
+    // Only the genuine votes were accumulated
+    assert_eq!(
+        accumulator.signers[&vote(0, 0).get_data_commitment()]
+            .1
+            .len(),
+        1
+    );
+    assert_eq!(
+        accumulator.signers[&vote(0, 1).get_data_commitment()]
+            .1
+            .len(),
+        1
+    );
+}
+
+/// If the signer bitvector doesn't cover the whole stake table, assembling the certificate
+/// fails, and the error says so instead of only passing on the assembler's.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_reports_assembly_precondition() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+    let stake_entries = membership.get_committee_qc_stake_table().len();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    // One bit short of the stake table
+    accumulator.num_signers = stake_entries - 1;
+
+    for node_id in 0..success_threshold - 1 {
+        assert!(accumulator
+            .try_accumulate(&vote(node_id), &membership)
+            .unwrap()
+            .is_left());
+    }
+    match accumulator.try_accumulate(&vote(success_threshold - 1), &membership) {
+        Err(VoteError::AssemblyFailed { violated, .. }) => assert_eq!(
+            violated,
+            AssemblyPrecondition::SignerCount {
+                signers: stake_entries - 1,
+                stake_entries,
+            }
+        ),
+        Err(e) => panic!("expected assembly to fail, got {e}"),
+        Ok(_) => panic!("assembled a certificate from a short signer bitvector"),
+    }
+}
+
+/// A batch with a forged vote falls back to validating each vote, so the forgery is skipped and
+/// the honest votes still form a certificate.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_in_batches() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+
+    // One short of 2f + 1, all valid
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    let votes: Vec<_> = (0..success_threshold - 1).map(vote).collect();
+    assert!(accumulator
+        .accumulate_batch(&votes, &membership)
+        .unwrap()
+        .is_left());
+    assert_eq!(
+        accumulator.signers[&votes[0].get_data_commitment()].1.len(),
+        votes.len()
+    );
+
+    // A forgery alongside the last honest vote
+    let mut forged = vote(success_threshold);
+    forged.signature.1 = vote(0).signature.1;
+    assert!(accumulator
+        .accumulate_batch(&[forged, vote(success_threshold - 1)], &membership)
+        .unwrap()
+        .is_right());
+}
+
+/// Votes accumulated against another epoch's stake table are rejected, so a certificate is only
+/// ever assembled against the stake table the accumulator was created for.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_vote_accumulation_rejects_other_epochs() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    // The next epoch drops the last node from the committee
+    let mut next_nodes = handle.hotshot.config.known_nodes_with_stake.clone();
+    next_nodes.pop();
+    let next_membership = <TestTypes as NodeType>::Membership::create_election(
+        next_nodes.clone(),
+        <TestTypes as NodeType>::Membership::default_election_config(next_nodes.len() as u64, 0),
+    );
+    assert_ne!(
+        membership.get_committee_qc_stake_table_commitment(),
+        next_membership.get_committee_qc_stake_table_commitment()
+    );
+
+    // The commitment is to the stake table itself
+    assert_eq!(
+        membership.get_committee_qc_stake_table_commitment(),
+        commit_to_stake_table(&membership.get_committee_qc_stake_table())
+    );
+
+    let vote = |node_id| -> ViewSyncCommitVote<TestTypes> {
+        signed_vote(
+            node_id,
+            ViewSyncCommitData {
+                relay: 0,
+                round: ViewNumber::new(1),
+            },
+        )
+    };
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+
+    assert!(accumulator
+        .try_accumulate(&vote(0), &membership)
+        .unwrap()
+        .is_left());
+    assert!(matches!(
+        accumulator.try_accumulate(&vote(1), &next_membership),
+        Err(VoteError::StakeTableMismatch)
+    ));
+    assert!(accumulator
+        .try_accumulate(&vote(2), &membership)
+        .unwrap()
+        .is_left());
+
+    // Only the votes from this epoch were accumulated
+    let commitment = vote(0).get_data_commitment();
+    let (signers, signatures) = &accumulator.signers[&commitment];
+    assert_eq!(signatures.len(), 2);
+    assert!(signers[0] && !signers[1] && signers[2]);
+}
+
+/// View sync commitments are domain-separated by phase, so a pre-commit signature can't be
+/// replayed as a commit vote for the same relay and round.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_view_sync_signatures_are_phase_bound() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.view_sync_membership.clone();
+
+    let precommit: ViewSyncPreCommitVote<TestTypes> = signed_vote(
+        0,
+        ViewSyncPreCommitData {
+            relay: 0,
+            round: ViewNumber::new(1),
+        },
+    );
+    let mut replayed: ViewSyncCommitVote<TestTypes> = signed_vote(
+        0,
+        ViewSyncCommitData {
+            relay: 0,
+            round: ViewNumber::new(1),
+        },
+    );
+    assert_ne!(
+        precommit.get_data_commitment().as_ref(),
+        replayed.get_data_commitment().as_ref()
+    );
+
+    // The pre-commit signature doesn't verify over the commit data
+    replayed.signature.1 = precommit.signature.1.clone();
+    assert!(!replayed.get_signing_key().validate(
+        &replayed.signature.1,
+        replayed.get_data_commitment().as_ref()
+    ));
+
+    // So the accumulator doesn't count it
+    let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+    assert!(accumulator
+        .accumulate(&replayed, &membership)
+        .unwrap()
+        .is_left());
+    assert!(accumulator.signers.is_empty());
+}
+
+/// Valid QCs are remembered, so seeing one again doesn't validate it again. Forgeries of them
+/// and invalid QCs are not remembered, and the least recently used QC is forgotten first.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_qc_cache() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+    let success_threshold = membership.success_threshold().get();
+
+    // A QC for the leaf with commitment `leaf_commit`, formed from the votes of a quorum
+    let qc = |leaf_commit| -> QuorumCertificate<TestTypes> {
+        let mut accumulator = accumulator::<_, QuorumCertificate<TestTypes>>(&membership);
+        (0..success_threshold)
+            .find_map(|node_id| {
+                accumulator
+                    .accumulate(
+                        &signed_vote(node_id, QuorumData { leaf_commit }),
+                        &membership,
+                    )
+                    .unwrap()
+                    .right()
+            })
+            .expect("a quorum of votes formed no certificate")
+    };
+    let genesis_qc = qc(Leaf::genesis(&TestInstanceState {}).commit());
+    let other_qc = qc(Commitment::<Leaf<TestTypes>>::default_commitment_no_preimage());
+
+    let mut cache = QcCache::new(NonZeroUsize::new(1).unwrap());
+    assert!(cache.is_empty());
+    assert!(cache.is_valid(&genesis_qc, &membership));
+    assert!(cache.contains(&genesis_qc));
+    assert!(cache.is_valid(&genesis_qc, &membership));
+    assert_eq!(cache.len(), 1);
+
+    // The signatures of one QC on the data of another
+    let mut forged = genesis_qc.clone();
+    forged.signatures.clone_from(&other_qc.signatures);
+    assert!(!cache.contains(&forged));
+    assert!(!cache.is_valid(&forged, &membership));
+    assert!(!cache.contains(&forged));
+
+    // Full, so the older QC makes way
+    assert!(cache.is_valid(&other_qc, &membership));
+    assert!(cache.contains(&other_qc));
+    assert!(!cache.contains(&genesis_qc));
+    assert_eq!(cache.len(), 1);
+}
+
+/// Accumulating a batch of votes should beat accumulating each in turn, since their signatures
+/// are validated together. Run with `--ignored --nocapture` to see the timings.
+#[cfg_attr(async_executor_impl = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[ignore]
+async fn bench_vote_accumulation_in_batches() {
+    const ROUNDS: u32 = 10;
+
+    let handle = build_system_handle(1).await.0;
+    let membership = handle.hotshot.memberships.quorum_membership.clone();
+
+    // One short of 2f + 1, so every vote is accumulated and no certificate is assembled
+    let votes: Vec<ViewSyncCommitVote<TestTypes>> = (0..membership.success_threshold().get() - 1)
+        .map(|node_id| {
+            signed_vote(
+                node_id,
+                ViewSyncCommitData {
+                    relay: 0,
+                    round: ViewNumber::new(1),
+                },
+            )
+        })
+        .collect();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+        for vote in &votes {
+            assert!(accumulator.accumulate(vote, &membership).unwrap().is_left());
+        }
+    }
+    let individually = start.elapsed() / ROUNDS;
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut accumulator = accumulator::<_, ViewSyncCommitCertificate2<TestTypes>>(&membership);
+        assert!(accumulator
+            .accumulate_batch(&votes, &membership)
+            .unwrap()
+            .is_left());
+    }
+    let batched = start.elapsed() / ROUNDS;
+
+    println!(
+        "accumulating {} votes: {individually:?} individually, {batched:?} batched ({:.1}x)",
+        votes.len(),
+        individually.as_secs_f64() / batched.as_secs_f64()
+    );
+    assert!(batched < individually);
+}
@@ -57,7 +57,7 @@ impl<TYPES: NodeType> ViewMessage<TYPES> for Message<TYPES> {
 pub struct Messages<TYPES: NodeType>(pub Vec<Message<TYPES>>);
 
 /// A message type agnostic description of a message's purpose
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum MessagePurpose {
     /// Message with a [quorum/DA] proposal.
     Proposal,
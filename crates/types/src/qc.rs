@@ -5,6 +5,7 @@ use crate::{
     stake_table::StakeTableEntry,
     traits::{qc::QuorumCertificateScheme, signature_key::SignatureKey},
 };
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::{
     fmt::Debug,
     format,
@@ -21,6 +22,7 @@ use jf_primitives::{
     signatures::AggregateableSignatureSchemes,
 };
 use serde::{Deserialize, Serialize};
+use snafu::Snafu;
 use typenum::U32;
 
 /// An implementation of QC using BLS signature and a bit-vector.
@@ -41,6 +43,226 @@ pub struct QCParams<K: SignatureKey, P: for<'a> Deserialize<'a>> {
     pub agg_sig_pp: P,
 }
 
+/// Why an assembled QC failed verification
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum QCVerificationError {
+    /// The signer bit vector doesn't line up with the stake table
+    #[snafu(display(
+        "signers bit vector len {signers} != the number of stake entries {stake_entries}"
+    ))]
+    SignersLengthMismatch {
+        /// The length of the signer bit vector
+        signers: usize,
+        /// The number of stake table entries
+        stake_entries: usize,
+    },
+    /// The message isn't the length the QC scheme signs over
+    #[snafu(display("message len {actual} != the expected length {expected}"))]
+    MessageLengthMismatch {
+        /// The length of the message
+        actual: usize,
+        /// The length of message the QC scheme signs over
+        expected: usize,
+    },
+    /// The signers don't hold enough stake to form a QC
+    #[snafu(display("total_weight {total_weight} less than threshold {threshold}"))]
+    InsufficientStake {
+        /// The stake held by the signers
+        total_weight: U256,
+        /// The stake needed to form a QC
+        threshold: U256,
+    },
+    /// The aggregated signature doesn't verify against the signers
+    #[snafu(display("invalid aggregated signature: {reason}"))]
+    InvalidSignature {
+        /// Why verification failed
+        reason: String,
+    },
+}
+
+/// Verify an assembled QC against the stake table and threshold in `params`. This is the
+/// counterpart to [`BitVectorQC::assemble`].
+///
+/// Returns the total stake of the signers.
+///
+/// # Errors
+/// - If `signers` doesn't line up with the stake table
+/// - If `message` is not 32 bytes long
+/// - If the signers don't meet the threshold
+/// - If the aggregated signature doesn't verify
+pub fn verify_assembled_qc<A>(
+    params: &QCParams<A::VerificationKey, A::PublicParameter>,
+    signers: &BitSlice,
+    sig: &A::Signature,
+    message: &[u8],
+) -> Result<U256, QCVerificationError>
+where
+    A: AggregateableSignatureSchemes<MessageUnit = u8> + Serialize + for<'a> Deserialize<'a>,
+    A::VerificationKey: SignatureKey,
+{
+    if signers.len() != params.stake_entries.len() {
+        return Err(QCVerificationError::SignersLengthMismatch {
+            signers: signers.len(),
+            stake_entries: params.stake_entries.len(),
+        });
+    }
+
+    let expected = <U32 as typenum::Unsigned>::USIZE;
+    if message.len() != expected {
+        return Err(QCVerificationError::MessageLengthMismatch {
+            actual: message.len(),
+            expected,
+        });
+    }
+
+    let total_weight: U256 = params
+        .stake_entries
+        .iter()
+        .zip(signers.iter())
+        .filter(|(_, signed)| **signed)
+        .fold(U256::zero(), |acc, (entry, _)| acc + entry.stake_amount);
+    if total_weight < params.threshold {
+        return Err(QCVerificationError::InsufficientStake {
+            total_weight,
+            threshold: params.threshold,
+        });
+    }
+
+    // The remaining checks in `check` are on the signature itself
+    BitVectorQC::<A>::check(
+        params,
+        GenericArray::from_slice(message),
+        &(sig.clone(), signers.to_bitvec()),
+    )
+    .map_err(|e| QCVerificationError::InvalidSignature {
+        reason: e.to_string(),
+    })
+}
+
+/// The version of the assembled QC byte layout produced by [`encode_assembled_qc`]
+pub const ASSEMBLED_QC_ENCODING_VERSION: u8 = 1;
+
+/// Why bytes failed to decode into an assembled QC
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum QCDecodingError {
+    /// The bytes were encoded with a layout we don't know
+    #[snafu(display("unsupported assembled QC encoding version {version}"))]
+    UnsupportedVersion {
+        /// The version the bytes claim to be encoded with
+        version: u8,
+    },
+    /// The bytes ended before the QC did
+    #[snafu(display("assembled QC is truncated"))]
+    Truncated,
+    /// The aggregated signature couldn't be decoded
+    #[snafu(display("invalid aggregated signature: {reason}"))]
+    InvalidAggregateSignature {
+        /// Why decoding failed
+        reason: String,
+    },
+    /// There were bytes left over after the QC
+    #[snafu(display("{remaining} trailing bytes after assembled QC"))]
+    TrailingBytes {
+        /// The number of bytes left over
+        remaining: usize,
+    },
+}
+
+/// Encode an assembled QC (the aggregated signature and the signer bit vector) for the wire.
+///
+/// The layout is:
+/// - `[0]`: [`ASSEMBLED_QC_ENCODING_VERSION`]
+/// - `[1..5]`: the number of bits in `signers`, as a little-endian `u32`
+/// - then `signers`, packed least significant bit first, with the last byte zero-padded
+/// - then the aggregated signature, canonically serialized in compressed form
+///
+/// The encoding is deterministic: the same signature and signers always produce the same bytes.
+///
+/// # Errors
+/// - If `signers` has more than `u32::MAX` bits
+/// - If the aggregated signature fails to serialize
+pub fn encode_assembled_qc<S: CanonicalSerialize>(
+    sig: &S,
+    signers: &BitSlice,
+) -> Result<Vec<u8>, SerializationError> {
+    let num_signers =
+        u32::try_from(signers.len()).map_err(|_| SerializationError::NotEnoughSpace)?;
+
+    let mut bytes = Vec::with_capacity(5 + (signers.len() + 7) / 8 + sig.compressed_size());
+    bytes.push(ASSEMBLED_QC_ENCODING_VERSION);
+    bytes.extend_from_slice(&num_signers.to_le_bytes());
+
+    // Pack explicitly rather than relying on `BitVec`'s in-memory layout
+    let mut packed = vec![0u8; (signers.len() + 7) / 8];
+    for index in signers.iter_ones() {
+        packed[index / 8] |= 1 << (index % 8);
+    }
+    bytes.extend_from_slice(&packed);
+
+    sig.serialize_compressed(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The format version of an assembled QC encoded by [`encode_assembled_qc`], read without
+/// decoding the rest, so that a receiver can pick how to decode and verify it.
+///
+/// # Errors
+/// - If `bytes` is empty
+/// - If the bytes were encoded with a version we don't know
+pub fn assembled_qc_format_version(bytes: &[u8]) -> Result<u8, QCDecodingError> {
+    let &version = bytes.first().ok_or(QCDecodingError::Truncated)?;
+    if version != ASSEMBLED_QC_ENCODING_VERSION {
+        return Err(QCDecodingError::UnsupportedVersion { version });
+    }
+
+    Ok(version)
+}
+
+/// Decode an assembled QC produced by [`encode_assembled_qc`] into the aggregated signature
+/// and the signer bit vector.
+///
+/// # Errors
+/// - If the bytes were encoded with an unknown version
+/// - If the bytes are truncated or have bytes left over
+/// - If the aggregated signature fails to deserialize
+pub fn decode_assembled_qc<S: CanonicalDeserialize>(
+    bytes: &[u8],
+) -> Result<(S, BitVec), QCDecodingError> {
+    assembled_qc_format_version(bytes)?;
+    let rest = &bytes[1..];
+
+    if rest.len() < 4 {
+        return Err(QCDecodingError::Truncated);
+    }
+    let (num_signers, rest) = rest.split_at(4);
+    let num_signers = u32::from_le_bytes(num_signers.try_into().expect("checked length above"));
+    let num_signers = usize::try_from(num_signers).map_err(|_| QCDecodingError::Truncated)?;
+
+    let packed_len = (num_signers + 7) / 8;
+    if rest.len() < packed_len {
+        return Err(QCDecodingError::Truncated);
+    }
+    let (packed, mut rest) = rest.split_at(packed_len);
+    let signers: BitVec = (0..num_signers)
+        .map(|index| packed[index / 8] & (1 << (index % 8)) != 0)
+        .collect();
+
+    let sig = S::deserialize_compressed(&mut rest).map_err(|e| {
+        QCDecodingError::InvalidAggregateSignature {
+            reason: e.to_string(),
+        }
+    })?;
+    if !rest.is_empty() {
+        return Err(QCDecodingError::TrailingBytes {
+            remaining: rest.len(),
+        });
+    }
+
+    Ok((sig, signers))
+}
+
 impl<A> QuorumCertificateScheme<A> for BitVectorQC<A>
 where
     A: AggregateableSignatureSchemes + Serialize + for<'a> Deserialize<'a>,
@@ -307,4 +529,137 @@ mod tests {
     fn test_quorum_certificate() {
         test_quorum_certificate!(BLSOverBN254CurveSignatureScheme);
     }
+
+    #[test]
+    fn test_verify_assembled_qc() {
+        type Scheme = BLSOverBN254CurveSignatureScheme;
+
+        let mut rng = jf_utils::test_rng();
+        let agg_sig_pp = Scheme::param_gen(Some(&mut rng)).unwrap();
+        let key_pairs: Vec<_> = (0..3).map(|_| KeyPair::generate(&mut rng)).collect();
+        let qc_pp = QCParams {
+            stake_entries: key_pairs
+                .iter()
+                .map(|key_pair| StakeTableEntry {
+                    stake_key: key_pair.ver_key(),
+                    stake_amount: U256::from(5u8),
+                })
+                .collect(),
+            threshold: U256::from(10u8),
+            agg_sig_pp,
+        };
+        let msg = [72u8; 32];
+        let sigs: Vec<_> = key_pairs
+            .iter()
+            .map(|key_pair| {
+                BitVectorQC::<Scheme>::sign(&agg_sig_pp, key_pair.sign_key_ref(), msg, &mut rng)
+                    .unwrap()
+            })
+            .collect();
+
+        // happy path
+        let signers = bitvec![1, 1, 0];
+        let (sig, _) = BitVectorQC::<Scheme>::assemble(&qc_pp, &signers, &sigs[..2]).unwrap();
+        assert_eq!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &signers, &sig, &msg).unwrap(),
+            U256::from(10u8)
+        );
+
+        // bad paths
+        assert!(matches!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &bitvec![1, 1], &sig, &msg),
+            Err(QCVerificationError::SignersLengthMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &signers, &sig, &msg[..31]),
+            Err(QCVerificationError::MessageLengthMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &bitvec![1, 0, 0], &sig, &msg),
+            Err(QCVerificationError::InsufficientStake { .. })
+        ));
+        assert!(matches!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &bitvec![0, 1, 1], &sig, &msg),
+            Err(QCVerificationError::InvalidSignature { .. })
+        ));
+        assert!(matches!(
+            verify_assembled_qc::<Scheme>(&qc_pp, &signers, &sig, &[70u8; 32]),
+            Err(QCVerificationError::InvalidSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn test_assembled_qc_encoding() {
+        type Scheme = BLSOverBN254CurveSignatureScheme;
+
+        let mut rng = jf_utils::test_rng();
+        let agg_sig_pp = Scheme::param_gen(Some(&mut rng)).unwrap();
+        let key_pairs: Vec<_> = (0..10).map(|_| KeyPair::generate(&mut rng)).collect();
+        let qc_pp = QCParams {
+            stake_entries: key_pairs
+                .iter()
+                .map(|key_pair| StakeTableEntry {
+                    stake_key: key_pair.ver_key(),
+                    stake_amount: U256::from(1u8),
+                })
+                .collect(),
+            threshold: U256::from(7u8),
+            agg_sig_pp,
+        };
+        let msg = [72u8; 32];
+        let sigs: Vec<_> = key_pairs
+            .iter()
+            .map(|key_pair| {
+                BitVectorQC::<Scheme>::sign(&agg_sig_pp, key_pair.sign_key_ref(), msg, &mut rng)
+                    .unwrap()
+            })
+            .collect();
+
+        // Spans more than one byte of signers, with a partially filled last byte
+        let signers = bitvec![1, 0, 1, 1, 1, 0, 1, 1, 1, 1];
+        let signer_sigs: Vec<_> = signers
+            .iter_ones()
+            .map(|index| sigs[index].clone())
+            .collect();
+        let qc = BitVectorQC::<Scheme>::assemble(&qc_pp, &signers, &signer_sigs).unwrap();
+
+        // round trip
+        let bytes = encode_assembled_qc(&qc.0, &qc.1).unwrap();
+        assert_eq!(bytes[0], ASSEMBLED_QC_ENCODING_VERSION);
+        assert_eq!(&bytes[1..5], &10u32.to_le_bytes());
+        assert_eq!(&bytes[5..7], &[0b1101_1101, 0b0000_0011]);
+        let decoded = decode_assembled_qc(&bytes).unwrap();
+        assert_eq!(decoded, qc);
+        assert!(verify_assembled_qc::<Scheme>(&qc_pp, &decoded.1, &decoded.0, &msg).is_ok());
+
+        // deterministic: assembling the same votes again produces identical bytes
+        let again = BitVectorQC::<Scheme>::assemble(&qc_pp, &signers, &signer_sigs).unwrap();
+        assert_eq!(encode_assembled_qc(&again.0, &again.1).unwrap(), bytes);
+
+        // bad paths
+        let mut bad_version = bytes.clone();
+        bad_version[0] = ASSEMBLED_QC_ENCODING_VERSION + 1;
+        assert!(matches!(
+            decode_assembled_qc::<<Scheme as SignatureScheme>::Signature>(&bad_version),
+            Err(QCDecodingError::UnsupportedVersion { .. })
+        ));
+        for len in [0, 3, 6] {
+            assert!(matches!(
+                decode_assembled_qc::<<Scheme as SignatureScheme>::Signature>(&bytes[..len]),
+                Err(QCDecodingError::Truncated)
+            ));
+        }
+        assert!(matches!(
+            decode_assembled_qc::<<Scheme as SignatureScheme>::Signature>(
+                &bytes[..bytes.len() - 1]
+            ),
+            Err(QCDecodingError::InvalidAggregateSignature { .. })
+        ));
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(
+            decode_assembled_qc::<<Scheme as SignatureScheme>::Signature>(&trailing),
+            Err(QCDecodingError::TrailingBytes { remaining: 1 })
+        ));
+    }
 }
@@ -1,13 +1,15 @@
 //! Types and structs for the hotshot signature keys
 
 use crate::{
-    qc::{BitVectorQC, QCParams},
+    qc::{verify_assembled_qc, BitVectorQC, QCParams},
     stake_table::StakeTableEntry,
     traits::{qc::QuorumCertificateScheme, signature_key::SignatureKey},
 };
+use ark_bn254::{Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::VariableBaseMSM;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use bitvec::{slice::BitSlice, vec::BitVec};
 use ethereum_types::U256;
-use generic_array::GenericArray;
 use jf_primitives::{
     errors::PrimitivesError,
     signatures::{
@@ -15,7 +17,7 @@ use jf_primitives::{
         SignatureScheme,
     },
 };
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use tracing::instrument;
 
@@ -26,6 +28,14 @@ pub type BLSPubKey = VerKey;
 /// Public parameters for BLS signature scheme
 pub type BLSPublicParam = ();
 
+/// Convert between two types with the same canonical encoding, such as a BLS key and the curve
+/// point it wraps. `from` is already valid, so the encoding is not checked again.
+fn recode<FROM: CanonicalSerialize, TO: CanonicalDeserialize>(from: &FROM) -> Option<TO> {
+    let mut bytes = Vec::new();
+    from.serialize_uncompressed(&mut bytes).ok()?;
+    TO::deserialize_uncompressed_unchecked(&*bytes).ok()
+}
+
 impl SignatureKey for BLSPubKey {
     type PrivateKey = BLSPrivKey;
     type StakeTableEntry = StakeTableEntry<VerKey>;
@@ -42,6 +52,50 @@ impl SignatureKey for BLSPubKey {
         BLSOverBN254CurveSignatureScheme::verify(&(), self, data, signature).is_ok()
     }
 
+    /// Checks a single pairing equation for the whole batch. Each signature and its key is
+    /// weighted by the same random 128-bit coefficient, so the weighted sums verify exactly
+    /// when every signature does, up to a 2^-128 chance.
+    fn batch_validate(
+        keys: &[Self],
+        signatures: &[Self::PureAssembledSignatureType],
+        data: &[u8],
+    ) -> bool {
+        if keys.len() != signatures.len() {
+            return false;
+        }
+        if keys.len() < 2 {
+            return keys
+                .iter()
+                .zip(signatures)
+                .all(|(key, signature)| key.validate(signature, data));
+        }
+
+        let mut rng = rand::thread_rng();
+        let coefficients: Vec<Fr> = (0..keys.len())
+            .map(|_| Fr::from(rng.gen::<u128>()))
+            .collect();
+        let Some(key_points) = keys.iter().map(recode).collect::<Option<Vec<G2Affine>>>() else {
+            return false;
+        };
+        let Some(signature_points) = signatures
+            .iter()
+            .map(recode)
+            .collect::<Option<Vec<G1Affine>>>()
+        else {
+            return false;
+        };
+        let (Ok(key), Ok(signature)) = (
+            G2Projective::msm(&key_points, &coefficients),
+            G1Projective::msm(&signature_points, &coefficients),
+        ) else {
+            return false;
+        };
+        match (recode::<_, Self>(&key), recode(&signature)) {
+            (Some(key), Some(signature)) => key.validate(&signature, data),
+            _ => false,
+        }
+    }
+
     fn sign(
         sk: &Self::PrivateKey,
         data: &[u8],
@@ -103,21 +157,21 @@ impl SignatureKey for BLSPubKey {
     }
 
     fn check(real_qc_pp: &Self::QCParams, data: &[u8], qc: &Self::QCType) -> bool {
-        let msg = GenericArray::from_slice(data);
-        BitVectorQC::<BLSOverBN254CurveSignatureScheme>::check(real_qc_pp, msg, qc).is_ok()
+        let (sig, signers) = qc;
+        verify_assembled_qc::<BLSOverBN254CurveSignatureScheme>(real_qc_pp, signers, sig, data)
+            .is_ok()
     }
 
     fn get_sig_proof(signature: &Self::QCType) -> (Self::PureAssembledSignatureType, BitVec) {
         signature.clone()
     }
 
-    fn assemble(
+    fn try_assemble(
         real_qc_pp: &Self::QCParams,
         signers: &BitSlice,
         sigs: &[Self::PureAssembledSignatureType],
-    ) -> Self::QCType {
+    ) -> Result<Self::QCType, PrimitivesError> {
         BitVectorQC::<BLSOverBN254CurveSignatureScheme>::assemble(real_qc_pp, signers, sigs)
-            .expect("this assembling shouldn't fail")
     }
 
     fn genesis_proposer_pk() -> Self {
@@ -125,3 +179,87 @@ impl SignatureKey for BLSPubKey {
         kp.ver_key()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jf_primitives::signatures::AggregateableSignatureSchemes;
+
+    /// A BLS signature
+    type Signature = <BLSPubKey as SignatureKey>::PureAssembledSignatureType;
+
+    /// The data every signature in these tests is over
+    const DATA: &[u8] = b"commitment";
+
+    /// `signature` negated, by way of the curve point it encodes
+    fn negate(signature: &Signature) -> Signature {
+        let mut bytes = Vec::new();
+        signature.serialize_compressed(&mut bytes).unwrap();
+        let point = -G1Affine::deserialize_compressed(&*bytes).unwrap();
+        bytes.clear();
+        point.serialize_compressed(&mut bytes).unwrap();
+        Signature::deserialize_compressed(&*bytes).unwrap()
+    }
+
+    #[test]
+    fn batch_rejects_cancelling_signatures() {
+        let keys: Vec<_> = (0..3)
+            .map(|index| BLSPubKey::generated_from_seed_indexed([0u8; 32], index))
+            .collect();
+        let signatures: Vec<_> = keys
+            .iter()
+            .map(|(_, private_key)| BLSPubKey::sign(private_key, DATA).unwrap())
+            .collect();
+        let (key_a, key_b, key_c) = (keys[0].0, keys[1].0, keys[2].0);
+
+        // Shift two valid signatures by the same amount in opposite directions. Neither is
+        // valid, but their sum is the sum of two valid signatures.
+        let offset = signatures[2].clone();
+        let forged_a = BLSOverBN254CurveSignatureScheme::aggregate(
+            &(),
+            &[key_a, key_c],
+            &[signatures[0].clone(), offset.clone()],
+        )
+        .unwrap();
+        let forged_b = BLSOverBN254CurveSignatureScheme::aggregate(
+            &(),
+            &[key_b, key_c],
+            &[signatures[1].clone(), negate(&offset)],
+        )
+        .unwrap();
+        assert!(!key_a.validate(&forged_a, DATA));
+        assert!(!key_b.validate(&forged_b, DATA));
+
+        assert!(BLSPubKey::batch_validate(
+            &[key_a, key_b],
+            &signatures[..2],
+            DATA
+        ));
+        assert!(!BLSPubKey::batch_validate(
+            &[key_a, key_b],
+            &[forged_a, forged_b],
+            DATA
+        ));
+    }
+
+    #[test]
+    fn batch_rejects_any_invalid_signature() {
+        let keys: Vec<_> = (0..8)
+            .map(|index| BLSPubKey::generated_from_seed_indexed([0u8; 32], index))
+            .collect();
+        let public_keys: Vec<_> = keys.iter().map(|(key, _)| *key).collect();
+        let mut signatures: Vec<_> = keys
+            .iter()
+            .map(|(_, private_key)| BLSPubKey::sign(private_key, DATA).unwrap())
+            .collect();
+        assert!(BLSPubKey::batch_validate(&public_keys, &signatures, DATA));
+        assert!(!BLSPubKey::batch_validate(
+            &public_keys[1..],
+            &signatures,
+            DATA
+        ));
+
+        signatures[5] = BLSPubKey::sign(&keys[5].1, b"other").unwrap();
+        assert!(!BLSPubKey::batch_validate(&public_keys, &signatures, DATA));
+    }
+}
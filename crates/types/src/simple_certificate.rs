@@ -1,9 +1,11 @@
 //! Implementations of the simple certificate type.  Used for Quorum, DA, and Timeout Certificates
 
 use std::{
+    collections::VecDeque,
     fmt::{self, Debug, Display, Formatter},
     hash::Hash,
     marker::PhantomData,
+    num::NonZeroUsize,
 };
 
 use commit::{Commitment, CommitmentBoundsArkless, Committable};
@@ -177,6 +179,84 @@ impl<TYPES: NodeType> QuorumCertificate<TYPES> {
     }
 }
 
+/// The number of quorum certificates a [`QcCache`] remembers by default
+pub const DEFAULT_QC_CACHE_CAPACITY: usize = 64;
+
+/// Remembers the quorum certificates we most recently found to be valid, so that one we see
+/// again (for example as the justify QC of a proposal that arrives over more than one network)
+/// isn't validated again. Certificates are keyed by their commitment, which covers their
+/// signatures, so a forgery of a cached certificate is not mistaken for it.
+///
+/// Whether a certificate is valid depends on the membership it is checked against, so a cache
+/// must only ever be used with one membership.
+#[derive(Clone, Debug)]
+pub struct QcCache<TYPES: NodeType> {
+    /// The commitments of the certificates we validated, least recently used first
+    validated: VecDeque<Commitment<QuorumCertificate<TYPES>>>,
+    /// The most certificates we remember. Past this, the least recently used is forgotten.
+    capacity: NonZeroUsize,
+}
+
+impl<TYPES: NodeType> Default for QcCache<TYPES> {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(DEFAULT_QC_CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN))
+    }
+}
+
+impl<TYPES: NodeType> QcCache<TYPES> {
+    /// Create an empty cache, remembering at most `capacity` certificates
+    #[must_use]
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            validated: VecDeque::with_capacity(capacity.get()),
+            capacity,
+        }
+    }
+
+    /// Whether `qc` is valid for `membership`. Only validates it if we haven't already; valid
+    /// certificates are remembered, invalid ones are not.
+    pub fn is_valid<MEMBERSHIP: Membership<TYPES>>(
+        &mut self,
+        qc: &QuorumCertificate<TYPES>,
+        membership: &MEMBERSHIP,
+    ) -> bool {
+        let commitment = qc.commit();
+        if let Some(position) = self.validated.iter().position(|seen| *seen == commitment) {
+            // Move it to the back, as the most recently used
+            self.validated.remove(position);
+            self.validated.push_back(commitment);
+            return true;
+        }
+
+        if !qc.is_valid_cert(membership) {
+            return false;
+        }
+        if self.validated.len() == self.capacity.get() {
+            self.validated.pop_front();
+        }
+        self.validated.push_back(commitment);
+        true
+    }
+
+    /// Whether we remember `qc` as valid, without validating it
+    #[must_use]
+    pub fn contains(&self, qc: &QuorumCertificate<TYPES>) -> bool {
+        self.validated.contains(&qc.commit())
+    }
+
+    /// The number of certificates we remember
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.validated.len()
+    }
+
+    /// Whether we remember no certificates
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.validated.is_empty()
+    }
+}
+
 /// Type alias for a `QuorumCertificate`, which is a `SimpleCertificate` of `QuorumVotes`
 pub type QuorumCertificate<TYPES> = SimpleCertificate<TYPES, QuorumData<TYPES>, SuccessThreshold>;
 /// Type alias for a DA certificate over `DAData`
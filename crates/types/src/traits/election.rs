@@ -5,8 +5,11 @@
 
 use super::node_implementation::NodeType;
 
-use crate::{traits::signature_key::SignatureKey, PeerConfig};
+use crate::{traits::signature_key::SignatureKey, utils::bincode_opts, PeerConfig};
 
+use bincode::Options;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use snafu::Snafu;
 use std::{collections::BTreeSet, fmt::Debug, hash::Hash, num::NonZeroU64};
 
@@ -21,6 +24,22 @@ pub enum ElectionError {
     MathError,
 }
 
+/// A commitment to a QC stake table, as computed by [`commit_to_stake_table`]
+pub type StakeTableCommitment = [u8; 32];
+
+/// Commit to a QC stake table: the SHA-256 digest of its wire encoding. Equal stake tables, in
+/// the same order, have equal commitments.
+///
+/// # Panics
+/// If the stake table fails to serialize
+#[must_use]
+pub fn commit_to_stake_table<ENTRY: Serialize>(stake_table: &[ENTRY]) -> StakeTableCommitment {
+    let bytes = bincode_opts()
+        .serialize(stake_table)
+        .expect("Serialization should not fail.");
+    Sha256::digest(bytes).into()
+}
+
 /// election config
 pub trait ElectionConfig:
     Default
@@ -55,6 +74,11 @@ pub trait Membership<TYPES: NodeType>:
         &self,
     ) -> Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>;
 
+    /// A commitment to the QC stake table, as computed by [`commit_to_stake_table`], so that
+    /// callers can tell whether two memberships share a stake table without comparing it entry
+    /// by entry. It is checked for every vote, so compute it once when the stake table is set.
+    fn get_committee_qc_stake_table_commitment(&self) -> StakeTableCommitment;
+
     /// The leader of the committee for view `view_number`.
     fn get_leader(&self, view_number: TYPES::Time) -> TYPES::SignatureKey;
 
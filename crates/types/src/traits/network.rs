@@ -64,6 +64,49 @@ pub enum PushCdnNetworkError {
     FailedToReceive,
     /// Failed to send a message to the server
     FailedToSend,
+    /// Lost the connection to the server while sending. Retryable: the client reconnects
+    #[snafu(display("lost connection to the server: {reason}"))]
+    ConnectionLost {
+        /// Why the connection was lost
+        reason: String,
+    },
+    /// Sending a message to the server took too long. Retryable
+    SendTimeout,
+    /// The marshal rejected (or failed to complete) our authentication. Handled by running the
+    /// marshal handshake again
+    #[snafu(display("failed to authenticate with the marshal: {reason}"))]
+    AuthenticationFailed {
+        /// Why authentication failed
+        reason: String,
+    },
+    /// Failed to subscribe to a topic
+    FailedToSubscribe,
+    /// Failed to unsubscribe from a topic
+    FailedToUnsubscribe,
+    /// Every node must stay subscribed to the global topic
+    CannotUnsubscribeFromGlobal,
+    /// The send queue was full, so the message was not queued
+    #[snafu(display("the send queue is full ({max_depth} sends waiting)"))]
+    SendQueueFull {
+        /// The most sends the queue holds
+        max_depth: usize,
+    },
+    /// A topic name isn't one of the topics the CDN knows
+    #[snafu(display("unknown topic {topic:?}, expected one of: {known}"))]
+    UnknownTopic {
+        /// The name we were given
+        topic: String,
+        /// The names of the topics we know
+        known: String,
+    },
+    /// A discovery endpoint was malformed
+    #[snafu(display("invalid discovery endpoint {endpoint}: {reason}"))]
+    InvalidDiscoveryEndpoint {
+        /// The endpoint we were given
+        endpoint: String,
+        /// What is wrong with it
+        reason: String,
+    },
 }
 
 /// Web server specific errors
@@ -137,6 +180,14 @@ pub enum NetworkError {
         /// Originating bincode error
         source: anyhow::Error,
     },
+    /// A serialized message was larger than the network will send
+    #[snafu(display("message of {size} bytes exceeds the limit of {limit} bytes"))]
+    MessageTooLarge {
+        /// The size of the serialized message, in bytes
+        size: usize,
+        /// The largest message the network will send, in bytes
+        limit: usize,
+    },
     /// Failed to deserealize a network message
     FailedToDeserialize {
         /// originating bincode error
@@ -152,6 +203,9 @@ pub enum NetworkError {
     ChannelSend,
     /// The underlying connection has been shut down
     ShutDown,
+    /// The channel we receive messages from has closed. This is terminal: retrying will not
+    /// help, and the network needs to be rebuilt
+    ReceiveChannelClosed,
     /// unable to cancel a request, the request has already been cancelled
     UnableToCancel,
     /// The requested data was not found
@@ -365,6 +419,9 @@ pub trait ConnectedNetwork<M: NetworkMsg, K: SignatureKey + 'static>:
 
     /// Sends a direct message to a specific node
     /// blocking
+    ///
+    /// Like every send, the message is serialized with `bind_version`, which is chosen per
+    /// call: during an upgrade, some messages can keep an old version while others move on.
     async fn direct_message<VER: StaticVersionType + 'static>(
         &self,
         message: M,
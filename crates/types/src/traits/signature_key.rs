@@ -1,4 +1,5 @@
 //! Minimal compatibility over public key signatures
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use bitvec::prelude::*;
 use ethereum_types::U256;
 use jf_primitives::errors::PrimitivesError;
@@ -67,7 +68,9 @@ pub trait SignatureKey:
         + PartialEq
         + Eq
         + Serialize
-        + for<'a> Deserialize<'a>;
+        + for<'a> Deserialize<'a>
+        + CanonicalSerialize
+        + CanonicalDeserialize;
     /// The type of the assembled qc: assembled signature + `BitVec`
     type QCType: Send
         + Sync
@@ -88,6 +91,25 @@ pub trait SignatureKey:
     /// Validate a signature
     fn validate(&self, signature: &Self::PureAssembledSignatureType, data: &[u8]) -> bool;
 
+    /// Validate signatures by each of `keys` (in order) over the same `data` at once. Returns
+    /// false if any of them is invalid, without saying which. By default each signature is
+    /// validated in turn.
+    ///
+    /// An override must still reject every invalid signature. Checking only the aggregate of the
+    /// signatures does not: signatures that are each invalid can cancel out in the sum. Each
+    /// signature must be weighted by a fresh random coefficient first, as `BLSPubKey` does.
+    fn batch_validate(
+        keys: &[Self],
+        signatures: &[Self::PureAssembledSignatureType],
+        data: &[u8],
+    ) -> bool {
+        keys.len() == signatures.len()
+            && keys
+                .iter()
+                .zip(signatures)
+                .all(|(key, signature)| key.validate(signature, data))
+    }
+
     /// Produce a signature
     /// # Errors
     /// If unable to sign the data with the key
@@ -128,11 +150,25 @@ pub trait SignatureKey:
     fn get_sig_proof(signature: &Self::QCType) -> (Self::PureAssembledSignatureType, BitVec);
 
     /// assemble the signature from the partial signature and the indication of signers in `BitVec`
+    /// # Errors
+    /// If the signers don't line up with the stake table or the signatures, or don't meet the
+    /// threshold
+    fn try_assemble(
+        real_qc_pp: &Self::QCParams,
+        signers: &BitSlice,
+        sigs: &[Self::PureAssembledSignatureType],
+    ) -> Result<Self::QCType, PrimitivesError>;
+
+    /// assemble the signature from the partial signature and the indication of signers in `BitVec`
+    /// # Panics
+    /// If assembly fails, see [`SignatureKey::try_assemble`]
     fn assemble(
         real_qc_pp: &Self::QCParams,
         signers: &BitSlice,
         sigs: &[Self::PureAssembledSignatureType],
-    ) -> Self::QCType;
+    ) -> Self::QCType {
+        Self::try_assemble(real_qc_pp, signers, sigs).expect("this assembling shouldn't fail")
+    }
 
     /// generates the genesis public key. Meant to be dummy/filler
     #[must_use]
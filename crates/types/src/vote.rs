@@ -2,20 +2,23 @@
 
 use std::{
     collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
     marker::PhantomData,
 };
 
-use bitvec::{bitvec, vec::BitVec};
+use bitvec::{bitvec, slice::BitSlice, vec::BitVec};
 use commit::Commitment;
 use either::Either;
 use ethereum_types::U256;
-use tracing::error;
+use jf_primitives::errors::PrimitivesError;
+use snafu::{ensure, ResultExt, Snafu};
+use tracing::{debug, error, warn};
 
 use crate::{
     simple_certificate::Threshold,
     simple_vote::Voteable,
     traits::{
-        election::Membership,
+        election::{Membership, StakeTableCommitment},
         node_implementation::NodeType,
         signature_key::{SignatureKey, StakeTableEntryType},
     },
@@ -73,6 +76,187 @@ pub trait Certificate<TYPES: NodeType>: HasViewNumber<TYPES> {
     /// Get the vote commitment which the votes commit to
     fn get_data_commitment(&self) -> Commitment<Self::Voteable>;
 }
+/// Evidence that a node signed votes for two different commitments in the same view
+#[derive(Clone, Debug)]
+pub struct Equivocation<TYPES: NodeType, COMMITMENT: Voteable> {
+    /// The key of the node that equivocated
+    pub key: TYPES::SignatureKey,
+    /// The view both votes were cast in
+    pub view: TYPES::Time,
+    /// The commitment the node voted for first
+    pub commitment_a: Commitment<COMMITMENT>,
+    /// The node's signature over `commitment_a`
+    pub signature_a: <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType,
+    /// The conflicting commitment the node voted for afterwards
+    pub commitment_b: Commitment<COMMITMENT>,
+    /// The node's signature over `commitment_b`
+    pub signature_b: <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType,
+}
+
+/// Errors creating a [`VoteAccumulator`]
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum VoteAccumulatorError {
+    /// The failure threshold (f + 1) is above the success threshold (2f + 1), so certificates
+    /// would form at the wrong stake
+    #[snafu(display(
+        "failure threshold {failure_threshold} exceeds success threshold {success_threshold}"
+    ))]
+    ThresholdsOutOfOrder {
+        /// The membership's success threshold
+        success_threshold: u64,
+        /// The membership's failure threshold
+        failure_threshold: u64,
+    },
+    /// A partial aggregate's signer bitvector doesn't have one bit per stake table entry
+    #[snafu(display(
+        "partial aggregate has {signers} signer bits for {stake_entries} stake table entries"
+    ))]
+    PartialSignerCount {
+        /// The length of the signer bitvector
+        signers: usize,
+        /// The number of entries in the stake table
+        stake_entries: usize,
+    },
+    /// A partial aggregate doesn't have exactly one signature per signer
+    #[snafu(display("partial aggregate has {signatures} signatures for {signers} signers"))]
+    PartialSignatureCount {
+        /// The number of signers
+        signers: usize,
+        /// The number of signatures
+        signatures: usize,
+    },
+    /// A signature in a partial aggregate isn't its signer's over the commitment
+    #[snafu(display("partial aggregate has an invalid signature from signer {signer}"))]
+    InvalidPartialSignature {
+        /// The signer's index in the stake table
+        signer: usize,
+    },
+}
+
+/// Why a vote was not accumulated, or why accumulating it failed
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum VoteError {
+    /// The vote's signature does not match its signing key and commitment
+    #[snafu(display("invalid vote signature"))]
+    InvalidSignature,
+    /// The signer has already voted for this commitment
+    #[snafu(display("duplicate vote"))]
+    DuplicateVote,
+    /// The signer already voted for a different commitment in this view. The vote has still
+    /// been accumulated, and the evidence recorded in `equivocations`.
+    #[snafu(display("signer equivocated"))]
+    Equivocation,
+    /// The signer has no stake, or is not in the stake table we accumulate for
+    #[snafu(display("vote is from a signer not in the stake table"))]
+    UnknownSigner,
+    /// The vote was accumulated against a different stake table (for example, another epoch's)
+    /// than the one the accumulator was created for
+    #[snafu(display("vote is against a different stake table than we accumulate for"))]
+    StakeTableMismatch,
+    /// We had enough votes, but failed to assemble them into a certificate
+    #[snafu(display("failed to assemble certificate, {violated}: {source}"))]
+    AssemblyFailed {
+        /// Which precondition of assembly the accumulated votes violated
+        violated: AssemblyPrecondition,
+        /// The error from assembling the signatures
+        source: PrimitivesError,
+    },
+}
+
+/// The preconditions for assembling a certificate, one of which was violated when assembly
+/// fails
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssemblyPrecondition {
+    /// The signer bitvector must have one bit per stake table entry
+    SignerCount {
+        /// The length of the signer bitvector
+        signers: usize,
+        /// The number of entries in the stake table
+        stake_entries: usize,
+    },
+    /// The signers' stake must reach the threshold
+    Threshold {
+        /// The total stake of the signers
+        signed_stake: U256,
+        /// The threshold the certificate was assembled for
+        threshold: U256,
+    },
+    /// There must be exactly one signature per signer
+    SignatureCount {
+        /// The number of signers in the bitvector
+        signers: usize,
+        /// The number of signatures
+        signatures: usize,
+    },
+    /// The inputs lined up, but the signatures failed to aggregate
+    Aggregation,
+}
+
+impl AssemblyPrecondition {
+    /// Work out which precondition of assembling `signatures` from `signers` against
+    /// `stake_table` at `threshold` is violated, checking them in the order assembly does. If
+    /// none is, the signatures themselves must have failed to aggregate.
+    fn diagnose<ENTRY: StakeTableEntryType>(
+        stake_table: &[ENTRY],
+        signers: &BitSlice,
+        signatures: usize,
+        threshold: U256,
+    ) -> Self {
+        if signers.len() != stake_table.len() {
+            return Self::SignerCount {
+                signers: signers.len(),
+                stake_entries: stake_table.len(),
+            };
+        }
+        let signed_stake = stake_table
+            .iter()
+            .zip(signers.iter())
+            .filter(|(_, signed)| **signed)
+            .fold(U256::zero(), |total, (entry, _)| total + entry.get_stake());
+        if signed_stake < threshold {
+            return Self::Threshold {
+                signed_stake,
+                threshold,
+            };
+        }
+        if signers.count_ones() != signatures {
+            return Self::SignatureCount {
+                signers: signers.count_ones(),
+                signatures,
+            };
+        }
+        Self::Aggregation
+    }
+}
+
+impl Display for AssemblyPrecondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SignerCount {
+                signers,
+                stake_entries,
+            } => write!(
+                f,
+                "signer bitvector has {signers} bits for {stake_entries} stake entries"
+            ),
+            Self::Threshold {
+                signed_stake,
+                threshold,
+            } => write!(
+                f,
+                "signers' stake {signed_stake} is below the threshold {threshold}"
+            ),
+            Self::SignatureCount {
+                signers,
+                signatures,
+            } => write!(f, "{signatures} signatures for {signers} signers"),
+            Self::Aggregation => write!(f, "signatures failed to aggregate"),
+        }
+    }
+}
+
 /// Mapping of vote commitment to signatures and bitvec
 type SignersMap<COMMITMENT, KEY> = HashMap<
     COMMITMENT,
@@ -96,6 +280,22 @@ pub struct VoteAccumulator<
     /// A bitvec to indicate which node is active and send out a valid signature for certificate aggregation, this automatically do uniqueness check
     /// And a list of valid signatures for certificate aggregation
     pub signers: SignersMap<Commitment<VOTE::Commitment>, TYPES::SignatureKey>,
+    /// The first vote accumulated from each node in the newest view we've accumulated a vote
+    /// for, used to detect equivocation. Earlier views are dropped as newer ones arrive.
+    pub first_votes: FirstVoteMap<TYPES, Commitment<VOTE::Commitment>>,
+    /// Every equivocation detected so far, with both signatures as evidence
+    pub equivocations: Vec<Equivocation<TYPES, VOTE::Commitment>>,
+    /// The QC stake table of the membership this accumulator was created for. Certificates are
+    /// only assembled against it, so votes accumulated against any other stake table are
+    /// rejected.
+    pub stake_table: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>,
+    /// The commitment to `stake_table`, which votes' memberships are checked against
+    stake_table_commitment: StakeTableCommitment,
+    /// The number of possible signers, i.e. the size of the QC stake table. Each bitvec in
+    /// `signers` has this many bits.
+    pub num_signers: usize,
+    /// The keys whose votes formed the most recent certificate, in stake table order
+    contributors: Vec<TYPES::SignatureKey>,
     /// Phantom data to specify the types this accumulator is for
     pub phantom: PhantomData<(TYPES, VOTE, CERT)>,
 }
@@ -103,69 +303,316 @@ pub struct VoteAccumulator<
 impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOTE::Commitment>>
     VoteAccumulator<TYPES, VOTE, CERT>
 {
+    /// Create an empty accumulator for votes from `membership`, with room for every signer in
+    /// its QC stake table.
+    ///
+    /// # Errors
+    /// If the membership's failure threshold is above its success threshold
+    pub fn new(membership: &TYPES::Membership) -> Result<Self, VoteAccumulatorError> {
+        let success_threshold = membership.success_threshold().get();
+        let failure_threshold = membership.failure_threshold().get();
+        ensure!(
+            failure_threshold <= success_threshold,
+            ThresholdsOutOfOrderSnafu {
+                success_threshold,
+                failure_threshold,
+            }
+        );
+
+        let stake_table = membership.get_committee_qc_stake_table();
+        Ok(Self {
+            vote_outcomes: HashMap::new(),
+            signers: HashMap::new(),
+            first_votes: HashMap::new(),
+            equivocations: Vec::new(),
+            num_signers: stake_table.len(),
+            stake_table_commitment: membership.get_committee_qc_stake_table_commitment(),
+            stake_table,
+            contributors: Vec::new(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Create an accumulator for votes from `membership` that carries on from a partial
+    /// aggregate of votes for `commitment`, such as the signers and signatures of a nearly
+    /// complete certificate received while catching up. `signers` has a bit per entry of the
+    /// QC stake table, and `signatures` holds the signers' signatures in stake table order.
+    /// Their stake is taken from the stake table. Further votes for `commitment` accumulate on
+    /// top of it, and form a certificate once the threshold is reached.
+    ///
+    /// The partial aggregate's signers are not checked for equivocation.
+    ///
+    /// # Errors
+    /// - If the membership's failure threshold is above its success threshold
+    /// - [`VoteAccumulatorError::PartialSignerCount`] if `signers` doesn't have a bit per stake
+    ///   table entry
+    /// - [`VoteAccumulatorError::PartialSignatureCount`] if there isn't one signature per signer
+    /// - [`VoteAccumulatorError::InvalidPartialSignature`] if a signature isn't its signer's
+    ///   over `commitment`
+    pub fn from_partial(
+        membership: &TYPES::Membership,
+        commitment: Commitment<VOTE::Commitment>,
+        signers: BitVec,
+        signatures: Vec<<TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType>,
+    ) -> Result<Self, VoteAccumulatorError> {
+        let mut accumulator = Self::new(membership)?;
+        ensure!(
+            signers.len() == accumulator.num_signers,
+            PartialSignerCountSnafu {
+                signers: signers.len(),
+                stake_entries: accumulator.num_signers,
+            }
+        );
+        ensure!(
+            signers.count_ones() == signatures.len(),
+            PartialSignatureCountSnafu {
+                signers: signers.count_ones(),
+                signatures: signatures.len(),
+            }
+        );
+
+        let mut stake = U256::zero();
+        let mut votes = BTreeMap::new();
+        for (signer, signature) in signers.iter_ones().zip(&signatures) {
+            let entry = &accumulator.stake_table[signer];
+            let key = TYPES::SignatureKey::get_public_key(entry);
+            ensure!(
+                key.validate(signature, commitment.as_ref()),
+                InvalidPartialSignatureSnafu { signer }
+            );
+            stake += entry.get_stake();
+            votes.insert(key, (signature.clone(), commitment));
+        }
+        debug!(
+            "Seeded accumulator with {} signers and {stake} stake",
+            signatures.len()
+        );
+
+        accumulator.vote_outcomes.insert(commitment, (stake, votes));
+        accumulator
+            .signers
+            .insert(commitment, (signers, signatures));
+        Ok(accumulator)
+    }
+
     /// Add a vote to the total accumulated votes.  Returns the accumulator or the certificate if we
     /// have accumulated enough votes to exceed the threshold for creating a certificate.
     ///
-    /// # Panics
-    /// Panics if the vote comes from a node not in the stake table
-    pub fn accumulate(&mut self, vote: &VOTE, membership: &TYPES::Membership) -> Either<(), CERT> {
+    /// Invalid votes, and votes from nodes not in the stake table, are logged and skipped; the
+    /// accumulator remains usable afterwards.
+    ///
+    /// A valid vote from a node that already voted for a different commitment in the same view
+    /// is recorded in `equivocations`, and then accumulated as usual.
+    ///
+    /// Once a certificate forms, the votes and signers accumulated for its commitment are
+    /// dropped from `vote_outcomes` and `signers`.
+    ///
+    /// # Errors
+    /// If we have enough votes but fail to assemble them into a certificate
+    pub fn accumulate(
+        &mut self,
+        vote: &VOTE,
+        membership: &TYPES::Membership,
+    ) -> Result<Either<(), CERT>, PrimitivesError> {
+        self.try_accumulate(vote, membership)
+            .or_else(|err| skip_vote(*vote.get_view_number(), err))
+    }
+
+    /// Add a vote to the total accumulated votes, like [`Self::accumulate`], but report why a
+    /// vote did not count instead of skipping it. The accumulator remains usable after any
+    /// error.
+    ///
+    /// A vote that completes a certificate returns it, even if the vote equivocates.
+    ///
+    /// # Errors
+    /// - [`VoteError::InvalidSignature`] if the vote's signature is invalid
+    /// - [`VoteError::StakeTableMismatch`] if `membership`'s stake table isn't the one the
+    ///   accumulator was created for
+    /// - [`VoteError::UnknownSigner`] if the signer is not in the stake table
+    /// - [`VoteError::DuplicateVote`] if the signer already voted for this commitment
+    /// - [`VoteError::Equivocation`] if the signer already voted for a different commitment in
+    ///   this view. The vote is still accumulated.
+    /// - [`VoteError::AssemblyFailed`] if we have enough votes but fail to assemble them into
+    ///   a certificate, naming the [`AssemblyPrecondition`] they violated
+    pub fn try_accumulate(
+        &mut self,
+        vote: &VOTE,
+        membership: &TYPES::Membership,
+    ) -> Result<Either<(), CERT>, VoteError> {
         let key = vote.get_signing_key();
+        if !key.validate(&vote.get_signature(), vote.get_data_commitment().as_ref()) {
+            return Err(VoteError::InvalidSignature);
+        }
+
+        self.accumulate_verified(vote, membership)
+    }
+
+    /// Add many votes to the total accumulated votes at once, like calling
+    /// [`Self::accumulate`] on each in turn, but validating the signatures of all votes for the
+    /// same commitment together with [`SignatureKey::batch_validate`]. Only if that fails are the
+    /// votes for that commitment validated one by one, to find the invalid ones.
+    ///
+    /// Returns the certificate as soon as one forms; the votes after the one that formed it are
+    /// not accumulated.
+    ///
+    /// # Errors
+    /// If we have enough votes but fail to assemble them into a certificate
+    pub fn accumulate_batch(
+        &mut self,
+        votes: &[VOTE],
+        membership: &TYPES::Membership,
+    ) -> Result<Either<(), CERT>, PrimitivesError> {
+        // Group the votes by commitment, keeping the order they first appear in
+        let mut by_commitment: Vec<(Commitment<VOTE::Commitment>, Vec<&VOTE>)> = Vec::new();
+        for vote in votes {
+            let commitment = vote.get_data_commitment();
+            match by_commitment.iter_mut().find(|(c, _)| *c == commitment) {
+                Some((_, group)) => group.push(vote),
+                None => by_commitment.push((commitment, vec![vote])),
+            }
+        }
+
+        for (commitment, group) in by_commitment {
+            let keys: Vec<_> = group.iter().map(|vote| vote.get_signing_key()).collect();
+            let signatures: Vec<_> = group.iter().map(|vote| vote.get_signature()).collect();
+            let verified =
+                TYPES::SignatureKey::batch_validate(&keys, &signatures, commitment.as_ref());
+            if !verified {
+                debug!(
+                    "Batch of {} votes failed validation, validating each",
+                    group.len()
+                );
+            }
+
+            for vote in group {
+                let outcome = if verified {
+                    self.accumulate_verified(vote, membership)
+                } else {
+                    self.try_accumulate(vote, membership)
+                };
+                let outcome = outcome.or_else(|err| skip_vote(*vote.get_view_number(), err))?;
+                if let Either::Right(cert) = outcome {
+                    return Ok(Either::Right(cert));
+                }
+            }
+        }
 
+        Ok(Either::Left(()))
+    }
+
+    /// Add a vote whose signature has already been validated to the total accumulated votes.
+    ///
+    /// # Errors
+    /// As [`Self::try_accumulate`], except that the signature is not checked
+    fn accumulate_verified(
+        &mut self,
+        vote: &VOTE,
+        membership: &TYPES::Membership,
+    ) -> Result<Either<(), CERT>, VoteError> {
+        let key = vote.get_signing_key();
         let vote_commitment = vote.get_data_commitment();
-        if !key.validate(&vote.get_signature(), vote_commitment.as_ref()) {
-            error!("Invalid vote! Vote Data {:?}", vote.get_data());
-            return Either::Left(());
+
+        // Mixing stake tables would assemble a certificate that verifies against neither
+        if membership.get_committee_qc_stake_table_commitment() != self.stake_table_commitment {
+            return Err(VoteError::StakeTableMismatch);
         }
 
         let Some(stake_table_entry) = membership.get_stake(&key) else {
-            return Either::Left(());
+            return Err(VoteError::UnknownSigner);
         };
-        let stake_table = membership.get_committee_qc_stake_table();
-        let vote_node_id = stake_table
+        let Some(vote_node_id) = self
+            .stake_table
             .iter()
-            .position(|x| *x == stake_table_entry.clone())
-            .unwrap();
+            .position(|x| TYPES::SignatureKey::get_public_key(x) == key)
+        else {
+            // A node with stake that is not in the QC stake table
+            return Err(VoteError::UnknownSigner);
+        };
+
+        // The QC stake table is what the certificate is verified against, so its stake is
+        // authoritative
+        let stake = self.stake_table[vote_node_id].get_stake();
+        if stake_table_entry.get_stake() != stake {
+            warn!(
+                "Membership stake {} for {key} disagrees with QC stake table stake {stake}, \
+                 using the latter",
+                stake_table_entry.get_stake()
+            );
+        }
 
         let original_signature: <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType =
             vote.get_signature();
 
+        // Check for duplicate vote
+        if self
+            .vote_outcomes
+            .get(&vote_commitment)
+            .is_some_and(|(_, votes)| votes.contains_key(&key))
+        {
+            return Err(VoteError::DuplicateVote);
+        }
+        // The stake table we were given may be larger than the one we were created for
+        if vote_node_id >= self.num_signers {
+            return Err(VoteError::UnknownSigner);
+        }
+        if self
+            .signers
+            .get(&vote_commitment)
+            .is_some_and(|(signers, _)| signers[vote_node_id])
+        {
+            return Err(VoteError::DuplicateVote);
+        }
+
+        // Only remember votes that count, so a vote we reject can't make its signer look like
+        // an equivocator
+        let equivocated = self.check_equivocation(vote, &key, vote_commitment);
+
         let (total_stake_casted, total_vote_map) = self
             .vote_outcomes
             .entry(vote_commitment)
             .or_insert_with(|| (U256::from(0), BTreeMap::new()));
-
-        // Check for duplicate vote
-        if total_vote_map.contains_key(&key) {
-            return Either::Left(());
-        }
         let (signers, sig_list) = self
             .signers
             .entry(vote_commitment)
-            .or_insert((bitvec![0; membership.total_nodes()], Vec::new()));
-        if signers.get(vote_node_id).as_deref() == Some(&true) {
-            error!("Node id is already in signers list");
-            return Either::Left(());
-        }
+            .or_insert((bitvec![0; self.num_signers], Vec::new()));
         signers.set(vote_node_id, true);
         sig_list.push(original_signature);
 
-        // TODO: Get the stake from the stake table entry.
-        *total_stake_casted += stake_table_entry.get_stake();
+        *total_stake_casted += stake;
         total_vote_map.insert(key, (vote.get_signature(), vote.get_data_commitment()));
 
-        if *total_stake_casted >= CERT::threshold(membership).into() {
+        let threshold = U256::from(CERT::threshold(membership));
+        debug!(
+            "Accumulated {total_stake_casted}/{threshold} stake for view {}",
+            *vote.get_view_number()
+        );
+
+        if *total_stake_casted >= threshold {
+            let contributors = signers
+                .iter_ones()
+                .map(|node_id| TYPES::SignatureKey::get_public_key(&self.stake_table[node_id]))
+                .collect();
+
             // Assemble QC
             let real_qc_pp: <<TYPES as NodeType>::SignatureKey as SignatureKey>::QCParams =
                 <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
-                    stake_table,
-                    U256::from(CERT::threshold(membership)),
+                    self.stake_table.clone(),
+                    threshold,
                 );
 
-            let real_qc_sig = <TYPES::SignatureKey as SignatureKey>::assemble(
+            let real_qc_sig = <TYPES::SignatureKey as SignatureKey>::try_assemble(
                 &real_qc_pp,
                 signers.as_bitslice(),
                 &sig_list[..],
-            );
+            )
+            .with_context(|_| AssemblyFailedSnafu {
+                violated: AssemblyPrecondition::diagnose(
+                    &self.stake_table,
+                    signers.as_bitslice(),
+                    sig_list.len(),
+                    threshold,
+                ),
+            })?;
 
             let cert = CERT::create_signed_certificate(
                 vote.get_data_commitment(),
@@ -173,11 +620,130 @@ impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOT
                 real_qc_sig,
                 vote.get_view_number(),
             );
-            return Either::Right(cert);
+            self.contributors = contributors;
+            self.clear_commitment(&vote_commitment);
+            return Ok(Either::Right(cert));
+        }
+        if equivocated {
+            return Err(VoteError::Equivocation);
+        }
+        Ok(Either::Left(()))
+    }
+
+    /// The keys of the nodes whose votes formed the most recent certificate returned by this
+    /// accumulator, in stake table order, i.e. the order of the certificate's signer bitvec.
+    /// Empty until a certificate forms.
+    #[must_use]
+    pub fn contributors(&self) -> Vec<TYPES::SignatureKey> {
+        self.contributors.clone()
+    }
+
+    /// How close the votes for `commitment` are to forming a certificate, as (stake
+    /// accumulated so far, stake needed). Nothing accumulated yet counts as zero.
+    pub fn accumulated_stake(
+        &self,
+        commitment: &Commitment<VOTE::Commitment>,
+        membership: &TYPES::Membership,
+    ) -> (U256, U256) {
+        let accumulated = self
+            .vote_outcomes
+            .get(commitment)
+            .map_or_else(U256::zero, |(stake, _)| *stake);
+
+        (accumulated, U256::from(CERT::threshold(membership)))
+    }
+
+    /// Drop everything accumulated towards a certificate for `commitment`, once it has formed.
+    /// Equivocation evidence, and the first votes it is checked against, are kept. Votes for
+    /// `commitment` arriving afterwards start again from zero.
+    fn clear_commitment(&mut self, commitment: &Commitment<VOTE::Commitment>) {
+        self.vote_outcomes.remove(commitment);
+        self.signers.remove(commitment);
+    }
+
+    /// Remember the first commitment `key` voted for in this vote's view, and record an
+    /// equivocation if `vote_commitment` conflicts with it. Returns whether a new equivocation
+    /// was recorded.
+    ///
+    /// Only the newest view is remembered: a vote for a newer view forgets the first votes of
+    /// older ones, and a vote for an older view isn't checked.
+    fn check_equivocation(
+        &mut self,
+        vote: &VOTE,
+        key: &TYPES::SignatureKey,
+        vote_commitment: Commitment<VOTE::Commitment>,
+    ) -> bool {
+        let view = vote.get_view_number();
+        if self.first_votes.keys().any(|newest| *newest > view) {
+            return false;
+        }
+        self.first_votes.retain(|first_view, _| *first_view == view);
+
+        let first_votes = self.first_votes.entry(view).or_default();
+        let Some((first_commitment, first_signature)) = first_votes.get(key).cloned() else {
+            first_votes.insert(key.clone(), (vote_commitment, vote.get_signature()));
+            return false;
+        };
+
+        // Repeating the first vote, or a conflicting vote we've already recorded, is not news
+        let already_recorded = self.equivocations.iter().any(|equivocation| {
+            equivocation.key == *key
+                && equivocation.view == view
+                && equivocation.commitment_b == vote_commitment
+        });
+        if first_commitment == vote_commitment || already_recorded {
+            return false;
+        }
+
+        self.equivocations.push(Equivocation {
+            key: key.clone(),
+            view,
+            commitment_a: first_commitment,
+            signature_a: first_signature,
+            commitment_b: vote_commitment,
+            signature_b: vote.get_signature(),
+        });
+        true
+    }
+}
+
+/// Log why a vote for `view` didn't count, and skip it, unless we failed to assemble a
+/// certificate, which is the only error [`VoteAccumulator::accumulate`] passes on.
+///
+/// # Errors
+/// The assembly error, if `err` is [`VoteError::AssemblyFailed`]
+fn skip_vote<CERT>(view: u64, err: VoteError) -> Result<Either<(), CERT>, PrimitivesError> {
+    match err {
+        VoteError::AssemblyFailed { violated, source } => {
+            error!("Failed to assemble certificate for view {view}, {violated}: {source}");
+            Err(source)
+        }
+        VoteError::Equivocation => {
+            warn!("Vote for view {view} equivocates, accumulated it anyway");
+            Ok(Either::Left(()))
+        }
+        VoteError::DuplicateVote => {
+            debug!("Skipping duplicate vote for view {view}");
+            Ok(Either::Left(()))
+        }
+        err => {
+            error!("Skipping vote for view {view}: {err}");
+            Ok(Either::Left(()))
         }
-        Either::Left(())
     }
 }
 
 /// Mapping of commitments to vote tokens by key.
 type VoteMap2<COMMITMENT, PK, SIG> = HashMap<COMMITMENT, (U256, BTreeMap<PK, (SIG, COMMITMENT)>)>;
+
+/// Mapping of views to the first commitment (and signature) each key voted for in that view.
+type FirstVoteMap<TYPES, COMMITMENT> = HashMap<
+    <TYPES as NodeType>::Time,
+    BTreeMap<
+        <TYPES as NodeType>::SignatureKey,
+        (
+            COMMITMENT,
+            <<TYPES as NodeType>::SignatureKey as SignatureKey>::PureAssembledSignatureType,
+        ),
+    >,
+>;